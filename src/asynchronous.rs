@@ -5,19 +5,35 @@
 //! await until buffer space or data becomes available, respectively.
 
 use futures::channel::mpsc::{channel, Receiver, Sender};
+use futures::lock::Mutex;
 use futures::StreamExt;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
 use std::slice;
+use std::sync::Arc;
 
+use crate::double_mapped_buffer::assume_init_mut;
+use crate::double_mapped_buffer::Backend;
+use crate::double_mapped_buffer::DoubleMappedBuffer;
+use crate::double_mapped_buffer::Options;
+use crate::double_mapped_buffer::Pod;
 use crate::generic;
 use crate::generic::CircularError;
+use crate::generic::GrowPolicy;
 use crate::generic::NoMetadata;
 use crate::generic::Notifier;
 
-struct AsyncNotifier {
+pub(crate) struct AsyncNotifier {
     chan: Sender<()>,
     armed: bool,
 }
 
+impl AsyncNotifier {
+    pub(crate) fn new(chan: Sender<()>) -> Self {
+        AsyncNotifier { chan, armed: false }
+    }
+}
+
 impl Notifier for AsyncNotifier {
     fn arm(&mut self) {
         self.armed = true;
@@ -46,14 +62,39 @@ impl Circular {
     ///
     /// The size is the least common multiple of the page size and the size of `T`.
     pub fn with_capacity<T>(min_items: usize) -> Result<Writer<T>, CircularError> {
-        let writer = generic::Circular::with_capacity(min_items)?;
+        Self::with_options(min_items, Options::default())
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with the underlying buffer configured through [Options].
+    pub fn with_options<T>(min_items: usize, options: Options) -> Result<Writer<T>, CircularError> {
+        let writer = generic::Circular::with_options(min_items, options)?;
+        Ok(Self::from_writer(writer))
+    }
 
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with memory that is provided by [Backend] `B`.
+    pub fn with_backend<T, B: Backend>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<Writer<T>, CircularError> {
+        let writer = generic::Circular::with_backend::<T, B, _, _>(min_items, options)?;
+        Ok(Self::from_writer(writer))
+    }
+
+    /// Create a circular buffer on top of an existing [DoubleMappedBuffer].
+    pub fn with_buffer<T>(buffer: DoubleMappedBuffer<T>) -> Result<Writer<T>, CircularError> {
+        let writer = generic::Circular::with_buffer(buffer)?;
+        Ok(Self::from_writer(writer))
+    }
+
+    fn from_writer<T>(writer: generic::Writer<T, AsyncNotifier, NoMetadata>) -> Writer<T> {
         let (tx, rx) = channel(1);
-        Ok(Writer {
+        Writer {
             writer,
             writer_sender: tx,
             chan: rx,
-        })
+        }
     }
 }
 
@@ -90,11 +131,28 @@ impl<T> Writer<T> {
     ///
     /// The future resolves once output space is available.
     /// The returned slice will never be empty.
-    pub async fn slice(&mut self) -> &mut [T] {
+    pub async fn slice(&mut self) -> &mut [T]
+    where
+        T: Pod,
+    {
+        unsafe { assume_init_mut(self.slice_uninit().await) }
+    }
+
+    /// Get a slice to the available output space as possibly uninitialized
+    /// items.
+    ///
+    /// The future resolves once output space is available. The returned slice
+    /// will never be empty. Written items are handed to the readers with
+    /// [assume_init](Writer::assume_init).
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::slice_uninit].
+    pub async unsafe fn slice_uninit(&mut self) -> &mut [MaybeUninit<T>] {
         // ugly workaround for borrow-checker problem
         // https://github.com/rust-lang/rust/issues/21906
         let (p, s) = loop {
-            match self.writer.slice(true) {
+            match self.writer.slice_uninit(true) {
                 [] => {
                     let _ = self.chan.next().await;
                 }
@@ -107,10 +165,38 @@ impl<T> Writer<T> {
     /// Get a slice to the free slots, available for writing.
     ///
     /// This function return immediately. The slice might be [empty](slice::is_empty).
-    pub fn try_slice(&mut self) -> &mut [T] {
+    pub fn try_slice(&mut self) -> &mut [T]
+    where
+        T: Pod,
+    {
         self.writer.slice(false)
     }
 
+    /// Get a slice to the free slots as possibly uninitialized items.
+    ///
+    /// This function return immediately. The slice might be [empty](slice::is_empty).
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::slice_uninit].
+    pub unsafe fn try_slice_uninit(&mut self) -> &mut [MaybeUninit<T>] {
+        self.writer.slice_uninit(false)
+    }
+
+    /// Resize the buffer, such that it can hold at least `min_items` items,
+    /// preserving unread items and readers.
+    ///
+    /// See [generic::Writer::resize].
+    pub fn resize(&mut self, min_items: usize) -> Result<(), CircularError> {
+        self.writer.resize(min_items)
+    }
+
+    /// Grow the buffer automatically, when the writer keeps blocking.
+    /// `None` disables growing.
+    pub fn grow_when_full(&mut self, policy: Option<GrowPolicy>) {
+        self.writer.grow_when_full(policy)
+    }
+
     /// Indicates that `n` items were written to the output buffer.
     ///
     /// It is ok if `n` is zero.
@@ -118,9 +204,151 @@ impl<T> Writer<T> {
     /// # Panics
     ///
     /// If produced more than space was available in the last provided slice.
-    pub fn produce(&mut self, n: usize) {
+    pub fn produce(&mut self, n: usize)
+    where
+        T: Pod,
+    {
         self.writer.produce(n, Vec::new());
     }
+
+    /// Indicates that the first `n` items of the last provided slice were
+    /// initialized.
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::assume_init].
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    pub unsafe fn assume_init(&mut self, n: usize) {
+        self.writer.assume_init(n, Vec::new());
+    }
+
+    /// Turn the writer into a [MultiWriter] that can be cloned to append
+    /// from several tasks.
+    ///
+    /// See [generic::Writer::into_multi].
+    pub fn into_multi(self) -> MultiWriter<T> {
+        let notifier = AsyncNotifier::new(self.writer_sender.clone());
+        MultiWriter {
+            writer: self.writer.into_multi(notifier),
+            writer_sender: self.writer_sender,
+            chan: Arc::new(Mutex::new(self.chan)),
+        }
+    }
+}
+
+/// Writer for an async circular buffer with items of type `T` that can be
+/// cloned to append items from several tasks.
+///
+/// Items are handed to the readers in the order of the reservations. See
+/// [generic::MultiWriter].
+pub struct MultiWriter<T> {
+    writer_sender: Sender<()>,
+    chan: Arc<Mutex<Receiver<()>>>,
+    writer: generic::MultiWriter<T, AsyncNotifier, NoMetadata>,
+}
+
+impl<T> Clone for MultiWriter<T> {
+    fn clone(&self) -> Self {
+        MultiWriter {
+            writer_sender: self.writer_sender.clone(),
+            chan: self.chan.clone(),
+            writer: self.writer.clone(),
+        }
+    }
+}
+
+impl<T: Pod> MultiWriter<T> {
+    /// Add a reader to the buffer.
+    ///
+    /// All readers can block the buffer, i.e., producers will only overwrite
+    /// data, if data was [consume](crate::asynchronous::Reader::consume)ed by
+    /// all readers.
+    pub fn add_reader(&self) -> Reader<T> {
+        let w_notifier = AsyncNotifier::new(self.writer_sender.clone());
+        let (tx, rx) = channel(1);
+        let r_notififer = AsyncNotifier::new(tx);
+
+        let reader = self.writer.add_reader(r_notififer, w_notifier);
+        Reader { reader, chan: rx }
+    }
+
+    /// The capacity of the buffer, i.e., the largest possible reservation.
+    pub fn capacity(&self) -> usize {
+        self.writer.capacity()
+    }
+
+    /// Reserve `n` items after all earlier reservations.
+    ///
+    /// The future resolves once enough space is available.
+    ///
+    /// Dropping a reservation without committing it, while later reservations
+    /// exist, hands zeroed items to the readers (see
+    /// [generic::MultiWriter::reserve]).
+    ///
+    /// # Panics
+    ///
+    /// If more than the [capacity](MultiWriter::capacity) is reserved.
+    pub async fn reserve(&self, n: usize) -> Reservation<T> {
+        loop {
+            if let Some(r) = self.try_reserve(n) {
+                return r;
+            }
+            // only one producer waits on the channel, the others on the lock
+            let mut chan = self.chan.lock().await;
+            if let Some(r) = self.writer.reserve(n, true) {
+                return Reservation { reservation: r };
+            }
+            let _ = chan.next().await;
+        }
+    }
+
+    /// Reserve `n` items after all earlier reservations, if there is enough
+    /// space right now.
+    ///
+    /// Dropping a reservation without committing it, while later reservations
+    /// exist, hands zeroed items to the readers (see
+    /// [generic::MultiWriter::reserve]).
+    ///
+    /// # Panics
+    ///
+    /// If more than the [capacity](MultiWriter::capacity) is reserved.
+    pub fn try_reserve(&self, n: usize) -> Option<Reservation<T>> {
+        self.writer
+            .reserve(n, false)
+            .map(|reservation| Reservation { reservation })
+    }
+}
+
+/// Items that are reserved by an async [MultiWriter].
+///
+/// See [generic::Reservation].
+pub struct Reservation<T> {
+    reservation: generic::Reservation<T, AsyncNotifier, NoMetadata>,
+}
+
+impl<T> Reservation<T> {
+    /// Hand the items to the readers, once all earlier reservations are
+    /// committed.
+    pub fn commit(self) {
+        self.reservation.commit(Vec::new());
+    }
+}
+
+impl<T> Deref for Reservation<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.reservation
+    }
+}
+
+impl<T> DerefMut for Reservation<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.reservation
+    }
 }
 
 /// Reader for an async circular buffer with items of type `T`.
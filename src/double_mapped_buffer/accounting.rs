@@ -0,0 +1,182 @@
+//! Process-wide accounting of the memory of all [DoubleMappedBuffers](super::DoubleMappedBuffer).
+//!
+//! Every buffer is registered with the size of its memory, the type of its
+//! items, and an optional [label](super::Options::label), when it is created
+//! and unregistered, when it is dropped. The live buffers can be
+//! [enumerated](buffers) to find out where memory is used.
+//!
+//! An optional [budget](set_budget) limits the memory of all buffers. Buffers
+//! that would exceed the budget are not created but fail with
+//! [Budget](super::DoubleMappedBufferError::Budget). For new buffers, the
+//! [planned](super::plan) size is reserved, before the memory is allocated.
+//!
+//! ```
+//! use vmcircbuffer::double_mapped_buffer::accounting;
+//! use vmcircbuffer::double_mapped_buffer::{DoubleMappedBuffer, Options};
+//!
+//! let options = Options::new().label("samples");
+//! let buffer = DoubleMappedBuffer::<f32>::with_options(1024, options).unwrap();
+//!
+//! let info = accounting::buffers()
+//!     .into_iter()
+//!     .find(|b| b.label() == Some("samples"))
+//!     .unwrap();
+//! assert_eq!(info.bytes(), buffer.capacity() * 4);
+//! assert!(accounting::total() >= info.bytes());
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use super::DoubleMappedBufferError;
+
+struct Registry {
+    budget: Option<usize>,
+    total: usize,
+    next_id: u64,
+    buffers: BTreeMap<u64, BufferInfo>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    budget: None,
+    total: 0,
+    next_id: 0,
+    buffers: BTreeMap::new(),
+});
+
+/// Information about a live buffer.
+#[derive(Clone, Debug)]
+pub struct BufferInfo {
+    id: u64,
+    label: Option<String>,
+    item: &'static str,
+    bytes: usize,
+}
+
+impl BufferInfo {
+    /// Unique identifier of the buffer within the process.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Label of the buffer, if set.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Name of the item type.
+    pub fn item(&self) -> &'static str {
+        self.item
+    }
+
+    /// Size of the memory of the buffer in bytes.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Size of the virtual address space of the buffer in bytes, which is
+    /// twice the size of its memory.
+    pub fn virtual_bytes(&self) -> usize {
+        2 * self.bytes
+    }
+}
+
+/// Limit the memory of all buffers to `bytes` bytes or remove the limit.
+///
+/// Buffers that exist already are not affected, even if they exceed the new
+/// budget.
+pub fn set_budget(bytes: Option<usize>) {
+    REGISTRY.lock().unwrap().budget = bytes;
+}
+
+/// Current budget in bytes.
+pub fn budget() -> Option<usize> {
+    REGISTRY.lock().unwrap().budget
+}
+
+/// Memory of all live buffers in bytes.
+pub fn total() -> usize {
+    REGISTRY.lock().unwrap().total
+}
+
+/// Live buffers, ordered by creation.
+pub fn buffers() -> Vec<BufferInfo> {
+    REGISTRY.lock().unwrap().buffers.values().cloned().collect()
+}
+
+/// Registration of a buffer that is removed, when it is dropped.
+#[derive(Debug)]
+pub(super) struct Account {
+    id: u64,
+}
+
+impl Account {
+    pub(super) fn register(
+        bytes: usize,
+        item: &'static str,
+        label: Option<String>,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let mut registry = REGISTRY.lock().unwrap();
+        if let Some(budget) = registry.budget {
+            if registry.total + bytes > budget {
+                return Err(DoubleMappedBufferError::Budget);
+            }
+        }
+
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.total += bytes;
+        registry.buffers.insert(
+            id,
+            BufferInfo {
+                id,
+                label,
+                item,
+                bytes,
+            },
+        );
+        Ok(Account { id })
+    }
+
+    /// Change the size of the buffer from the size that was registered, e.g.,
+    /// once the memory is allocated. Fails, if growing exceeds the budget.
+    pub(super) fn resize(&self, bytes: usize) -> Result<(), DoubleMappedBufferError> {
+        let mut registry = REGISTRY.lock().unwrap();
+        let registry = &mut *registry;
+        let Some(info) = registry.buffers.get_mut(&self.id) else {
+            return Ok(());
+        };
+        if let Some(budget) = registry.budget {
+            if bytes > info.bytes && registry.total - info.bytes + bytes > budget {
+                return Err(DoubleMappedBufferError::Budget);
+            }
+        }
+        registry.total = registry.total - info.bytes + bytes;
+        info.bytes = bytes;
+        Ok(())
+    }
+
+    pub(super) fn set_label(&self, label: Option<String>) {
+        if let Some(info) = REGISTRY.lock().unwrap().buffers.get_mut(&self.id) {
+            info.label = label;
+        }
+    }
+
+    pub(super) fn label(&self) -> Option<String> {
+        REGISTRY
+            .lock()
+            .unwrap()
+            .buffers
+            .get(&self.id)
+            .and_then(|b| b.label.clone())
+    }
+}
+
+impl Drop for Account {
+    fn drop(&mut self) {
+        let mut registry = REGISTRY.lock().unwrap();
+        if let Some(info) = registry.buffers.remove(&self.id) {
+            registry.total -= info.bytes;
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::sync::{Arc, Mutex};
+
+use super::buffer_size;
+use super::pagesize;
+use super::unix::create_fd;
+use super::Backend;
+use super::DoubleMappedBuffer;
+use super::DoubleMappedBufferError;
+use super::HugePagePolicy;
+use super::Options;
+
+/// Allocator that packs many [DoubleMappedBuffers](DoubleMappedBuffer) into
+/// one memory file and one reserved address range.
+///
+/// Every buffer that is created on its own has its own file descriptor and
+/// mappings. Applications with thousands of small buffers can, therefore, run
+/// into the limit for the number of mappings (`vm.max_map_count`). Buffers of
+/// an arena share the file descriptor of the arena and are placed next to each
+/// other, which allows the kernel to merge their mappings.
+///
+/// The arena is kept alive until it and all its buffers are dropped. When a
+/// buffer is dropped, its memory is returned to the arena.
+///
+/// ```
+/// # use vmcircbuffer::double_mapped_buffer::Arena;
+/// let arena = Arena::new(1 << 20).unwrap();
+/// let a = arena.buffer::<f32>(1024).unwrap();
+/// let b = arena.buffer::<u8>(4096).unwrap();
+/// assert!(a.capacity() >= 1024);
+/// ```
+#[derive(Clone)]
+pub struct Arena {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// Start of the reserved address range, which is twice the size of the
+    /// file.
+    addr: usize,
+    pages: usize,
+    fd: OwnedFd,
+    /// Label of the buffers in the [accounting](super::accounting).
+    label: Option<String>,
+    /// Free extents of the file as map from first page to number of pages.
+    free: Mutex<BTreeMap<usize, usize>>,
+}
+
+impl Arena {
+    /// Create an arena with `bytes` bytes, rounded up to the page size, that
+    /// can be handed out to buffers.
+    pub fn new(bytes: usize) -> Result<Self, DoubleMappedBufferError> {
+        Self::with_options(bytes, Options::default())
+    }
+
+    /// Create an arena with `bytes` bytes, with the memory file configured
+    /// through [Options].
+    ///
+    /// The [Backing](super::Backing) selects the memory file, the
+    /// [label](Options::label) is used for all buffers of the arena, and
+    /// [max_bytes](Options::max_bytes) limits the size of the arena. Arenas are
+    /// not backed by huge pages, i.e., they fail with
+    /// [HugePages](DoubleMappedBufferError::HugePages) or fall back to regular
+    /// pages, depending on the [HugePagePolicy]. Locking, prefaulting, NUMA
+    /// policies, and keeping the file descriptor fail with
+    /// [Unsupported](DoubleMappedBufferError::Unsupported).
+    pub fn with_options(bytes: usize, options: Options) -> Result<Self, DoubleMappedBufferError> {
+        if options.huge_pages.is_some() && options.huge_page_policy == HugePagePolicy::Require {
+            return Err(DoubleMappedBufferError::HugePages(
+                io::ErrorKind::Unsupported.into(),
+            ));
+        }
+        if options.lock || options.prefault || options.numa.is_some() || options.keep_fd {
+            return Err(DoubleMappedBufferError::Unsupported);
+        }
+
+        let ps = pagesize();
+        let pages = std::cmp::max(bytes.div_ceil(ps), 1);
+        let size = pages * ps;
+        if let Some(limit) = options.max_bytes {
+            if size > limit {
+                return Err(DoubleMappedBufferError::TooLarge { bytes: size, limit });
+            }
+        }
+
+        let fd = create_fd(options.backing, None)?;
+        unsafe {
+            if libc::ftruncate(fd.as_raw_fd(), size as libc::off_t) < 0 {
+                return Err(DoubleMappedBufferError::Truncate(io::Error::last_os_error()));
+            }
+
+            let addr = libc::mmap(
+                std::ptr::null_mut::<libc::c_void>(),
+                2 * size,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            );
+            if addr == libc::MAP_FAILED {
+                return Err(DoubleMappedBufferError::Placeholder(
+                    io::Error::last_os_error(),
+                ));
+            }
+
+            Ok(Arena {
+                inner: Arc::new(Inner {
+                    addr: addr as usize,
+                    pages,
+                    fd,
+                    label: options.label,
+                    free: Mutex::new(BTreeMap::from([(0, pages)])),
+                }),
+            })
+        }
+    }
+
+    /// Create a buffer in the arena that can hold at least `min_items` items.
+    ///
+    /// The capacity is determined like for [DoubleMappedBuffer::new]. Fails
+    /// with [Exhausted](DoubleMappedBufferError::Exhausted), if the arena
+    /// has no free space of this size.
+    pub fn buffer<T>(
+        &self,
+        min_items: usize,
+    ) -> Result<DoubleMappedBuffer<T>, DoubleMappedBufferError> {
+        if mem::align_of::<T>() > pagesize() {
+            return Err(DoubleMappedBufferError::Alignment);
+        }
+        let pages = buffer_size(min_items, mem::size_of::<T>(), pagesize())? / pagesize();
+        let backend = self.inner.clone().allocate(pages)?;
+        let options = Options {
+            label: self.inner.label.clone(),
+            ..Options::default()
+        };
+        DoubleMappedBuffer::from_backend(Box::new(backend), &options, None)
+    }
+
+    /// Size of the arena in bytes.
+    pub fn size(&self) -> usize {
+        self.inner.pages * pagesize()
+    }
+
+    /// Number of bytes that are not used by buffers.
+    ///
+    /// Since buffers have to be contiguous, this is not necessarily the size of
+    /// the largest buffer that can be created.
+    pub fn available(&self) -> usize {
+        self.inner.free.lock().unwrap().values().sum::<usize>() * pagesize()
+    }
+}
+
+impl Inner {
+    /// Take `pages` pages from the first free extent that is large enough and
+    /// map them twice.
+    fn allocate(self: Arc<Self>, pages: usize) -> Result<ArenaBackend, DoubleMappedBufferError> {
+        let page = {
+            let mut free = self.free.lock().unwrap();
+            let (&start, &len) = free
+                .iter()
+                .find(|(_, &len)| len >= pages)
+                .ok_or(DoubleMappedBufferError::Exhausted)?;
+            free.remove(&start);
+            if len > pages {
+                free.insert(start + pages, len - pages);
+            }
+            start
+        };
+
+        let mut backend = ArenaBackend {
+            arena: self,
+            page,
+            pages,
+            mapped: false,
+        };
+        backend.map()?;
+        Ok(backend)
+    }
+
+    /// Return an extent to the free list, merging it with its neighbors.
+    fn release(&self, mut page: usize, mut pages: usize) {
+        let mut free = self.free.lock().unwrap();
+        if let Some((&start, &len)) = free.range(..page).next_back() {
+            if start + len == page {
+                free.remove(&start);
+                page = start;
+                pages += len;
+            }
+        }
+        if let Some(len) = free.remove(&(page + pages)) {
+            pages += len;
+        }
+        free.insert(page, pages);
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.addr as *mut libc::c_void, 2 * self.pages * pagesize());
+        }
+    }
+}
+
+/// [Backend] for a buffer in an [Arena].
+///
+/// The buffer with `pages` pages, starting at page `page` of the memory file,
+/// is mapped at page `2 * page` of the reserved address range, i.e., the
+/// address ranges of the buffers do not overlap.
+pub struct ArenaBackend {
+    arena: Arc<Inner>,
+    page: usize,
+    pages: usize,
+    /// Both halves are mapped, i.e., the buffer was handed out.
+    mapped: bool,
+}
+
+impl ArenaBackend {
+    fn map(&mut self) -> Result<(), DoubleMappedBufferError> {
+        let size = self.size_bytes();
+        let offset = (self.page * pagesize()) as libc::off_t;
+
+        for (half, err) in [
+            (0, DoubleMappedBufferError::MapFirst as fn(io::Error) -> _),
+            (1, DoubleMappedBufferError::MapSecond),
+        ] {
+            let ret = unsafe {
+                libc::mmap(
+                    (self.addr() + half * size) as *mut libc::c_void,
+                    size,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED | libc::MAP_FIXED,
+                    self.arena.fd.as_raw_fd(),
+                    offset,
+                )
+            };
+            if ret == libc::MAP_FAILED {
+                return Err(err(io::Error::last_os_error()));
+            }
+        }
+        self.mapped = true;
+        Ok(())
+    }
+}
+
+unsafe impl Backend for ArenaBackend {
+    /// Buffers can only be created through [Arena::buffer].
+    fn allocate(
+        _min_items: usize,
+        _item_size: usize,
+        _alignment: usize,
+        _options: &Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        Err(DoubleMappedBufferError::Unsupported)
+    }
+
+    fn addr(&self) -> usize {
+        self.arena.addr + 2 * self.page * pagesize()
+    }
+
+    fn size_bytes(&self) -> usize {
+        self.pages * pagesize()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Drop for ArenaBackend {
+    fn drop(&mut self) {
+        let size = self.size_bytes();
+        unsafe {
+            // free the memory or, if this is not possible, zero it for the next buffer
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            let freed = libc::fallocate(
+                self.arena.fd.as_raw_fd(),
+                libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                (self.page * pagesize()) as libc::off_t,
+                size as libc::off_t,
+            ) == 0;
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            let freed = false;
+            if !freed && self.mapped {
+                std::ptr::write_bytes(self.addr() as *mut u8, 0, size);
+            }
+
+            // put the reservation back in place
+            libc::mmap(
+                self.addr() as *mut libc::c_void,
+                2 * size,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE | libc::MAP_FIXED,
+                -1,
+                0,
+            );
+        }
+        self.arena.release(self.page, self.pages);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::compiler_fence;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn buffers() {
+        let ps = pagesize();
+        let arena = Arena::new(8 * ps).unwrap();
+        assert_eq!(arena.size(), 8 * ps);
+
+        let a = arena.buffer::<u8>(ps).unwrap();
+        let b = arena.buffer::<u32>(ps / 2).unwrap();
+        assert_eq!(a.capacity(), ps);
+        assert_eq!(b.capacity() * 4, 2 * ps);
+        assert_eq!(arena.available(), 5 * ps);
+
+        unsafe {
+            a.slice_mut()[3] = 42;
+            b.slice_mut()[3] = 23;
+            compiler_fence(Ordering::SeqCst);
+            assert_eq!(a.slice_with_offset(a.capacity())[3], 42);
+            assert_eq!(b.slice_with_offset(b.capacity())[3], 23);
+        }
+
+        assert!(matches!(
+            arena.buffer::<u8>(6 * ps),
+            Err(DoubleMappedBufferError::Exhausted)
+        ));
+    }
+
+    #[test]
+    fn release() {
+        let ps = pagesize();
+        let arena = Arena::new(4 * ps).unwrap();
+
+        let a = arena.buffer::<u8>(ps).unwrap();
+        let b = arena.buffer::<u8>(ps).unwrap();
+        let c = arena.buffer::<u8>(2 * ps).unwrap();
+        assert_eq!(arena.available(), 0);
+        unsafe { b.slice_mut()[0] = 42 };
+
+        drop(a);
+        drop(b);
+        assert_eq!(arena.available(), 2 * ps);
+
+        // freed extents are merged and their memory is zeroed
+        let d = arena.buffer::<u8>(2 * ps).unwrap();
+        unsafe {
+            assert!(d.slice().iter().all(|v| *v == 0));
+        }
+
+        // buffers keep the arena alive
+        drop(arena);
+        unsafe {
+            c.slice_mut()[0] = 1;
+            assert_eq!(c.slice_with_offset(c.capacity())[0], 1);
+        }
+        drop(d);
+    }
+
+    #[test]
+    fn options() {
+        let ps = pagesize();
+        for options in [
+            Options::new().lock(true),
+            Options::new().prefault(true),
+            Options::new().keep_fd(true),
+        ] {
+            assert!(matches!(
+                Arena::with_options(ps, options),
+                Err(DoubleMappedBufferError::Unsupported)
+            ));
+        }
+        assert!(matches!(
+            Arena::with_options(2 * ps, Options::new().max_bytes(ps)),
+            Err(DoubleMappedBufferError::TooLarge { .. })
+        ));
+
+        let arena = Arena::with_options(ps, Options::new().label("arena-options")).unwrap();
+        let a = arena.buffer::<u8>(ps).unwrap();
+        assert!(super::super::accounting::buffers()
+            .iter()
+            .any(|b| b.label() == Some("arena-options") && b.bytes() == ps));
+        drop(a);
+    }
+}
@@ -0,0 +1,101 @@
+use std::any::Any;
+#[cfg(unix)]
+use std::os::unix::io::BorrowedFd;
+
+use super::Advice;
+use super::DoubleMappedBufferError;
+use super::HugePageSize;
+use super::Options;
+
+/// Provides the memory of a [DoubleMappedBuffer](super::DoubleMappedBuffer).
+///
+/// The crate ships with the [SystemBackend](super::SystemBackend), which maps
+/// memory through the facilities of the operating system. Custom backends can
+/// be used to provide memory from other sources or to implement test doubles.
+///
+/// # Safety
+///
+/// Implementations have to guarantee that
+/// - [addr](Backend::addr) points to `2 * size_bytes` bytes that stay valid for
+///   reads and, unless [read_only](Backend::read_only), writes until the
+///   backend is dropped,
+/// - byte `i` and byte `i + size_bytes` refer to the same memory, i.e., the
+///   second half mirrors the first half, or, if this is emulated, that both
+///   halves are consistent after a range is [mirrored](Backend::mirror),
+/// - `addr` and `size_bytes` do not change over the lifetime of the backend.
+pub unsafe trait Backend: Send + Sync + 'static {
+    /// Allocate memory for at least `min_items` items of size `item_size` and
+    /// alignment `alignment`.
+    fn allocate(
+        min_items: usize,
+        item_size: usize,
+        alignment: usize,
+        options: &Options,
+    ) -> Result<Self, DoubleMappedBufferError>
+    where
+        Self: Sized;
+
+    /// Start address of the first mapping.
+    fn addr(&self) -> usize;
+
+    /// Size of one mapping in bytes.
+    fn size_bytes(&self) -> usize;
+
+    /// Make `len` bytes that were written starting at byte `offset` visible in
+    /// both halves of the memory.
+    ///
+    /// This is a no-op for backends that map the memory twice. Backends that
+    /// emulate the double mapping copy the range to the other half.
+    ///
+    /// # Safety
+    ///
+    /// `offset` has to be smaller than and `len` at most
+    /// [size_bytes](Backend::size_bytes). The range and its mirror must not be
+    /// accessed concurrently.
+    unsafe fn mirror(&self, offset: usize, len: usize) {
+        let _ = (offset, len);
+    }
+
+    /// Write modified memory back to the file that backs it, if any.
+    ///
+    /// If `wait` is set, the call blocks until the data is written.
+    fn flush(&self, wait: bool) -> Result<(), DoubleMappedBufferError> {
+        let _ = wait;
+        Ok(())
+    }
+
+    /// Apply `advice` to both halves of the memory.
+    ///
+    /// Fails with [Unsupported](DoubleMappedBufferError::Unsupported), if the
+    /// backend does not support the advice.
+    fn advise(&self, advice: Advice) -> Result<(), DoubleMappedBufferError> {
+        let _ = advice;
+        Err(DoubleMappedBufferError::Unsupported)
+    }
+
+    /// Whether the memory is mapped read-only.
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    /// The size of the huge pages that back the memory, if any.
+    fn huge_pages(&self) -> Option<HugePageSize> {
+        None
+    }
+
+    /// File descriptor that refers to the memory, if any.
+    #[cfg(unix)]
+    fn fd(&self) -> Option<BorrowedFd<'_>> {
+        None
+    }
+
+    /// The backend as [Any], which allows to downcast it to its concrete type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl dyn Backend {
+    /// Returns the backend as `B`, if it has this type.
+    pub fn downcast_ref<B: Backend>(&self) -> Option<&B> {
+        self.as_any().downcast_ref::<B>()
+    }
+}
@@ -0,0 +1,158 @@
+use std::mem;
+
+use super::DoubleMappedBufferError;
+use super::Options;
+
+/// Capacity of a buffer as returned by [plan].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capacity {
+    items: usize,
+    bytes: usize,
+}
+
+impl Capacity {
+    /// Number of items that fit into the buffer.
+    pub fn items(&self) -> usize {
+        self.items
+    }
+
+    /// Size of the buffer in bytes.
+    ///
+    /// The buffer is mapped twice, i.e., it uses twice this size of virtual
+    /// address space.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+/// Capacity of a buffer for at least `min_items` items of type `T`, without
+/// allocating it.
+///
+/// The size of the buffer is the smallest multiple of the granularity, i.e.,
+/// the [page size](super::pagesize) or the [huge page size](Options::huge_pages),
+/// and the size of `T` that can hold `min_items` items. For item sizes that
+/// share few factors with the page size, this can be much larger than
+/// requested.
+///
+/// This is the capacity that buffers get, which are created through
+/// [DoubleMappedBuffer::with_options](super::DoubleMappedBuffer::with_options)
+/// and the `with_options` constructors of the circular buffers. If huge pages
+/// are not available and the [HugePagePolicy](super::HugePagePolicy) allows
+/// falling back to normal pages, the capacity is smaller. Custom
+/// [Backends](super::Backend) can also deviate.
+///
+/// ```
+/// use vmcircbuffer::double_mapped_buffer::{pagesize, plan, Options};
+///
+/// let capacity = plan::<[u8; 24]>(1, &Options::new()).unwrap();
+/// assert_eq!(capacity.bytes() % pagesize(), 0);
+/// assert_eq!(capacity.bytes() % 24, 0);
+/// assert_eq!(capacity.items() * 24, capacity.bytes());
+/// ```
+pub fn plan<T>(min_items: usize, options: &Options) -> Result<Capacity, DoubleMappedBufferError> {
+    let item_size = mem::size_of::<T>();
+    if item_size == 0 {
+        return Err(DoubleMappedBufferError::Layout);
+    }
+    let bytes = buffer_size(min_items, item_size, options.granularity())?;
+    Ok(Capacity {
+        items: bytes / item_size,
+        bytes,
+    })
+}
+
+/// Smallest multiple of the page size `ps` and the item size that can hold at
+/// least `min_items` items.
+pub(crate) fn buffer_size(
+    min_items: usize,
+    item_size: usize,
+    ps: usize,
+) -> Result<usize, DoubleMappedBufferError> {
+    let lcm = (ps / gcd(ps, item_size))
+        .checked_mul(item_size)
+        .ok_or(DoubleMappedBufferError::Overflow)?;
+    let bytes = min_items
+        .checked_mul(item_size)
+        .ok_or(DoubleMappedBufferError::Overflow)?;
+    std::cmp::max(bytes.div_ceil(lcm), 1)
+        .checked_mul(lcm)
+        .ok_or(DoubleMappedBufferError::Overflow)
+}
+
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::double_mapped_buffer::pagesize;
+    use crate::double_mapped_buffer::DoubleMappedBuffer;
+    use crate::double_mapped_buffer::HugePageSize;
+
+    /// Reference implementation that steps through multiples of the page size.
+    fn stepped(min_items: usize, item_size: usize, ps: usize) -> usize {
+        let mut size = ps;
+        while size < min_items * item_size || !size.is_multiple_of(item_size) {
+            size += ps;
+        }
+        size
+    }
+
+    #[test]
+    fn buffer_size_matches_stepping() {
+        for item_size in [1, 3, 4, 8, 24, 100, 4095, 4097, 8192] {
+            for min_items in [0, 1, 100, 1000, 12345] {
+                assert_eq!(
+                    buffer_size(min_items, item_size, 4096).unwrap(),
+                    stepped(min_items, item_size, 4096)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn plan_capacity() {
+        let ps = pagesize();
+        let c = plan::<u32>(1, &Options::new()).unwrap();
+        assert_eq!(c.bytes(), ps);
+        assert_eq!(c.items(), ps / 4);
+
+        let c = plan::<u8>(ps + 1, &Options::new()).unwrap();
+        assert_eq!(c.bytes(), 2 * ps);
+
+        let options = Options::new().huge_pages(HugePageSize::Size2M);
+        let c = plan::<u32>(1, &options).unwrap();
+        assert_eq!(c.bytes(), 1 << 21);
+
+        assert!(matches!(
+            plan::<()>(1, &Options::new()),
+            Err(DoubleMappedBufferError::Layout)
+        ));
+        assert!(matches!(
+            plan::<u64>(usize::MAX / 4, &Options::new()),
+            Err(DoubleMappedBufferError::Overflow)
+        ));
+
+        let b = DoubleMappedBuffer::<[u8; 24]>::new(1000).unwrap();
+        let c = plan::<[u8; 24]>(1000, &Options::new()).unwrap();
+        assert_eq!(b.capacity(), c.items());
+    }
+
+    #[test]
+    fn max_bytes() {
+        let ps = pagesize();
+        let options = Options::new().max_bytes(ps);
+        assert!(DoubleMappedBuffer::<u8>::with_options(ps, options.clone()).is_ok());
+        match DoubleMappedBuffer::<u8>::with_options(ps + 1, options) {
+            Err(DoubleMappedBufferError::TooLarge { bytes, limit }) => {
+                assert_eq!(bytes, 2 * ps);
+                assert_eq!(limit, ps);
+            }
+            _ => panic!("buffer exceeds limit"),
+        }
+    }
+}
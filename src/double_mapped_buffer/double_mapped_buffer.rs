@@ -1,17 +1,44 @@
 use std::marker::PhantomData;
 use std::mem;
+use std::mem::MaybeUninit;
+#[cfg(unix)]
+use std::os::unix::io::{AsFd, BorrowedFd, OwnedFd};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(all(unix, not(target_os = "android")))]
+use std::path::Path;
 use std::slice;
 
+use super::accounting::Account;
+#[cfg(unix)]
+use super::Access;
+use super::Advice;
+use super::Backend;
+use super::DefaultBackend;
 use super::DoubleMappedBufferError;
-use super::DoubleMappedBufferImpl;
+use super::HugePageSize;
+use super::Options;
+use super::Pod;
+use super::Regions;
+#[cfg(unix)]
+use super::SystemBackend;
 
 /// A buffer that is mapped twice, back-to-back in the virtual address space of the process.
 ///
 /// This struct is supposed to be used as a base for buffer implementations that
 /// want to exploit the consequtive mappings to present available buffer space
 /// sequentially, without having to worry about wrapping.
+///
+/// The memory is provided by a [Backend], which is the [DefaultBackend], unless
+/// the buffer is created with [new_in](DoubleMappedBuffer::new_in) or
+/// [with_backend](DoubleMappedBuffer::with_backend).
+///
+/// All buffers are registered in the process-wide [accounting](super::accounting).
 pub struct DoubleMappedBuffer<T> {
-    buffer: DoubleMappedBufferImpl,
+    backend: Box<dyn Backend>,
+    account: Account,
+    addr: usize,
+    capacity: usize,
     _p: PhantomData<T>,
 }
 
@@ -21,14 +48,325 @@ impl<T> DoubleMappedBuffer<T> {
     /// The acutal capacity of the buffer will be the smallest multiple of the
     /// system page size and the item size that can hold at least `min_items`
     /// items.
+    /// It can be determined upfront with [plan](super::plan).
     pub fn new(min_items: usize) -> Result<Self, DoubleMappedBufferError> {
-        match DoubleMappedBufferImpl::new(min_items, mem::size_of::<T>(), mem::align_of::<T>()) {
-            Ok(buffer) => Ok(DoubleMappedBuffer {
-                buffer,
-                _p: PhantomData,
-            }),
-            Err(e) => Err(e),
+        Self::with_options(min_items, Options::default())
+    }
+
+    /// Create a buffer that can hold at least `min_items` items, configured
+    /// through [Options].
+    ///
+    /// If huge pages are requested, the capacity is the smallest multiple of
+    /// the huge page size and the item size that can hold at least
+    /// `min_items` items.
+    pub fn with_options(
+        min_items: usize,
+        options: Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        Self::new_in::<DefaultBackend>(min_items, options)
+    }
+
+    /// Create a buffer that can hold at least `min_items` items with memory
+    /// that is [allocated](Backend::allocate) by backend `B`.
+    pub fn new_in<B: Backend>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let account = Self::reserve(min_items, &options)?;
+        let backend = B::allocate(
+            min_items,
+            mem::size_of::<T>(),
+            mem::align_of::<T>(),
+            &options,
+        )?;
+        Self::from_backend(Box::new(backend), &options, Some(account))
+    }
+
+    /// Check the planned size of a new buffer against the limits and reserve
+    /// it in the accounting, before the memory is allocated.
+    ///
+    /// The reservation is released, when the returned [Account] is dropped,
+    /// e.g., because the allocation failed.
+    fn reserve(min_items: usize, options: &Options) -> Result<Account, DoubleMappedBufferError> {
+        let bytes = super::plan::<T>(min_items, options)?.bytes();
+        Self::reserve_bytes(bytes, options)
+    }
+
+    /// Check `bytes` against the limits and reserve them in the accounting.
+    fn reserve_bytes(bytes: usize, options: &Options) -> Result<Account, DoubleMappedBufferError> {
+        if let Some(limit) = options.max_bytes {
+            if bytes > limit {
+                return Err(DoubleMappedBufferError::TooLarge { bytes, limit });
+            }
+        }
+        Account::register(bytes, std::any::type_name::<T>(), options.label.clone())
+    }
+
+    /// Create a buffer on top of memory that is provided by `backend`.
+    ///
+    /// Fails with [Layout](DoubleMappedBufferError::Layout), if the size of
+    /// the memory is not a multiple of the size of `T`, and with
+    /// [Alignment](DoubleMappedBufferError::Alignment), if the memory is not
+    /// aligned for `T`.
+    pub fn with_backend(backend: impl Backend) -> Result<Self, DoubleMappedBufferError> {
+        Self::from_backend(Box::new(backend), &Options::default(), None)
+    }
+
+    /// Set up the buffer on top of the memory of `backend`. The memory is
+    /// accounted in `account`, if it was reserved before it was allocated.
+    pub(super) fn from_backend(
+        backend: Box<dyn Backend>,
+        options: &Options,
+        account: Option<Account>,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let addr = backend.addr();
+        let size_bytes = backend.size_bytes();
+        let item_size = mem::size_of::<T>();
+
+        if item_size == 0 || size_bytes == 0 || !size_bytes.is_multiple_of(item_size) {
+            return Err(DoubleMappedBufferError::Layout);
+        }
+        if !addr.is_multiple_of(mem::align_of::<T>()) {
+            return Err(DoubleMappedBufferError::Alignment);
+        }
+        // backends might round differently than planned
+        if let Some(limit) = options.max_bytes {
+            if size_bytes > limit {
+                return Err(DoubleMappedBufferError::TooLarge {
+                    bytes: size_bytes,
+                    limit,
+                });
+            }
+        }
+        let account = match account {
+            Some(account) => {
+                account.resize(size_bytes)?;
+                account
+            }
+            None => Account::register(
+                size_bytes,
+                std::any::type_name::<T>(),
+                options.label.clone(),
+            )?,
+        };
+
+        Ok(DoubleMappedBuffer {
+            backend,
+            account,
+            addr,
+            capacity: size_bytes / item_size,
+            _p: PhantomData,
+        })
+    }
+
+    /// Create a named buffer that can hold at least `min_items` items.
+    ///
+    /// The buffer is created as POSIX shared memory object (`shm_open`) under
+    /// `name`, which can be [opened](DoubleMappedBuffer::open_named) by other
+    /// processes. A leading `/` is added to the name, if it is missing. The
+    /// name is removed, once the buffer is dropped. Processes that opened the
+    /// buffer can continue to use it.
+    ///
+    /// Named buffers are not backed by huge pages.
+    #[cfg(all(unix, not(target_os = "android")))]
+    pub fn create_named(
+        name: &str,
+        min_items: usize,
+        options: Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let account = Self::reserve(min_items, &options)?;
+        let backend = SystemBackend::create_named(
+            name,
+            min_items,
+            mem::size_of::<T>(),
+            mem::align_of::<T>(),
+            &options,
+        )?;
+        Self::from_backend(Box::new(backend), &options, Some(account))
+    }
+
+    /// Open a buffer that was [created](DoubleMappedBuffer::create_named) under
+    /// `name`, possibly by another process.
+    ///
+    /// Fails with [Layout](DoubleMappedBufferError::Layout), if size or
+    /// alignment of `T` do not match the item type of the buffer.
+    #[cfg(all(unix, not(target_os = "android")))]
+    pub fn open_named(name: &str) -> Result<Self, DoubleMappedBufferError> {
+        let backend = SystemBackend::open_named(name, mem::size_of::<T>(), mem::align_of::<T>())?;
+        Self::with_backend(backend)
+    }
+
+    /// Remove the `name` of a named buffer.
+    ///
+    /// This is only required to clean up after a process crashed, since the
+    /// name is otherwise removed when the creating buffer is dropped.
+    #[cfg(all(unix, not(target_os = "android")))]
+    pub fn unlink_named(name: &str) -> Result<(), DoubleMappedBufferError> {
+        SystemBackend::unlink_named(name)
+    }
+
+    /// Open the buffer that is stored in the file at `path` or create the file
+    /// with a buffer that can hold at least `min_items` items.
+    ///
+    /// Contrary to [named](DoubleMappedBuffer::create_named) buffers, the file
+    /// is not removed, when the buffer is dropped. Its content survives
+    /// restarts of the process and, once [flushed](DoubleMappedBuffer::flush),
+    /// crashes of the system. Like named buffers, the file starts with a header
+    /// page. Fails with [Layout](DoubleMappedBufferError::Layout), if an
+    /// existing file holds items of a different type.
+    ///
+    /// The size is checked against [max_bytes](Options::max_bytes) and the
+    /// [budget](super::accounting::set_budget), before the file is created. A file that
+    /// was created is removed again, if opening the buffer fails.
+    #[cfg(all(unix, not(target_os = "android")))]
+    pub fn open_file(
+        path: impl AsRef<Path>,
+        min_items: usize,
+        options: Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let path = path.as_ref();
+        if mem::size_of::<T>() == 0 {
+            return Err(DoubleMappedBufferError::Layout);
         }
+        // files are not backed by huge pages, an existing file might differ in size
+        let bytes = super::buffer_size(min_items, mem::size_of::<T>(), super::pagesize())?;
+        let account = Self::reserve_bytes(bytes, &options)?;
+        let (backend, created) = SystemBackend::open_file(
+            path,
+            true,
+            min_items,
+            mem::size_of::<T>(),
+            mem::align_of::<T>(),
+            &options,
+        )?;
+        Self::from_backend(Box::new(backend), &options, Some(account)).inspect_err(|_| {
+            if created {
+                let _ = std::fs::remove_file(path);
+            }
+        })
+    }
+
+    /// Open the buffer that is stored in the existing file at `path`.
+    ///
+    /// Like [open_file](DoubleMappedBuffer::open_file) but fails with
+    /// [Open](DoubleMappedBufferError::Open), if the file does not exist,
+    /// instead of creating it.
+    #[cfg(all(unix, not(target_os = "android")))]
+    pub fn open_existing_file(
+        path: impl AsRef<Path>,
+        options: Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let path = path.as_ref();
+        // the file holds a header page, followed by the buffer
+        let bytes = std::fs::metadata(path)
+            .map_err(DoubleMappedBufferError::Open)?
+            .len();
+        let bytes = (bytes as usize).saturating_sub(super::pagesize());
+        let account = Self::reserve_bytes(bytes, &options)?;
+        let (backend, _) = SystemBackend::open_file(
+            path,
+            false,
+            0,
+            mem::size_of::<T>(),
+            mem::align_of::<T>(),
+            &options,
+        )?;
+        Self::from_backend(Box::new(backend), &options, Some(account))
+    }
+
+    /// Apply a hint about the use of the buffer memory (`madvise`).
+    ///
+    /// With [DontNeed](Advice::DontNeed) and [Free](Advice::Free), the pages
+    /// of the buffer are released, which discards its content. Accessing the
+    /// buffer afterwards is fine and faults in new pages. Buffers that are
+    /// stored in a [file](DoubleMappedBuffer::open_file) or imported from a
+    /// file descriptor keep their content, since the file is not modified.
+    /// Only the pages that are mapped into the process are released.
+    pub fn advise(&self, advice: Advice) -> Result<(), DoubleMappedBufferError> {
+        self.backend.advise(advice)
+    }
+
+    /// Write the buffer back to the file that backs it (`msync`) and wait until
+    /// this is completed.
+    ///
+    /// This is only relevant for buffers that are stored in a
+    /// [file](DoubleMappedBuffer::open_file).
+    pub fn flush(&self) -> Result<(), DoubleMappedBufferError> {
+        self.backend.flush(true)
+    }
+
+    /// Initiate writing the buffer back to the file that backs it, without
+    /// waiting for completion.
+    pub fn flush_async(&self) -> Result<(), DoubleMappedBufferError> {
+        self.backend.flush(false)
+    }
+
+    /// Map the buffer that is backed by `fd` twice into the address space.
+    ///
+    /// The file descriptor could, for example, be
+    /// [received](DoubleMappedBuffer::receive) from another process or be
+    /// duplicated from another buffer. The capacity of the buffer is derived
+    /// from the size of the file. Fails with
+    /// [Layout](DoubleMappedBufferError::Layout), if the size is not a multiple
+    /// of the page size and the size of `T` or, for named buffers, if the item
+    /// layout does not match.
+    ///
+    /// With [Access::ReadOnly], the buffer is mapped read-only. Writing to it
+    /// crashes the process.
+    #[cfg(unix)]
+    pub fn from_fd(fd: OwnedFd, access: Access) -> Result<Self, DoubleMappedBufferError> {
+        let backend =
+            SystemBackend::from_fd(fd, mem::size_of::<T>(), mem::align_of::<T>(), access)?;
+        Self::with_backend(backend)
+    }
+
+    /// Double-map `len` bytes of memory that is provided through `fd`, starting
+    /// at `offset`.
+    ///
+    /// This allows to import memory that is allocated by someone else, e.g., a
+    /// kernel ring buffer of a driver that is exported as dma-buf or UIO
+    /// region. Contrary to [from_fd](DoubleMappedBuffer::from_fd), the size of
+    /// the file is not considered and no header is expected. `offset` and
+    /// `len` have to be multiples of the [page size](super::pagesize) and `len`
+    /// a multiple of the size of `T`.
+    #[cfg(unix)]
+    pub fn from_fd_range(
+        fd: OwnedFd,
+        offset: usize,
+        len: usize,
+        access: Access,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let backend = SystemBackend::from_fd_range(
+            fd,
+            offset,
+            len,
+            mem::size_of::<T>(),
+            mem::align_of::<T>(),
+            access,
+        )?;
+        Self::with_backend(backend)
+    }
+
+    /// Send the file descriptor of the buffer over a Unix domain socket
+    /// (`SCM_RIGHTS`).
+    ///
+    /// The receiver can map the buffer with [receive](DoubleMappedBuffer::receive).
+    /// Fails with [Unsupported](DoubleMappedBufferError::Unsupported), if the
+    /// backend does not provide a file descriptor, e.g., for anonymous buffers
+    /// that were not created with [keep_fd](Options::keep_fd).
+    #[cfg(unix)]
+    pub fn send(&self, socket: &UnixStream) -> Result<(), DoubleMappedBufferError> {
+        let fd = self.fd().ok_or(DoubleMappedBufferError::Unsupported)?;
+        super::unix::send_fd(socket.as_fd(), fd)
+    }
+
+    /// Receive the file descriptor of a buffer that was
+    /// [sent](DoubleMappedBuffer::send) over a Unix domain socket and map it
+    /// with the given `access` rights.
+    #[cfg(unix)]
+    pub fn receive(socket: &UnixStream, access: Access) -> Result<Self, DoubleMappedBufferError> {
+        let fd = super::unix::recv_fd(socket.as_fd())?;
+        Self::from_fd(fd, access)
     }
 
     /// Returns the slice corresponding to the first mapping of the buffer.
@@ -37,21 +375,19 @@ impl<T> DoubleMappedBuffer<T> {
     ///
     /// Provides raw access to the slice.
     pub unsafe fn slice(&self) -> &[T] {
-        let addr = self.buffer.addr();
-        debug_assert_eq!(addr % mem::align_of::<T>(), 0);
-        slice::from_raw_parts(addr as *const T, self.buffer.capacity())
+        slice::from_raw_parts(self.addr as *const T, self.capacity)
     }
 
     /// Returns the mutable slice corresponding to the first mapping of the buffer.
     ///
     /// # Safety
     ///
-    /// Provides raw access to the slice.
+    /// Provides raw access to the slice. The buffer must not be
+    /// [read-only](DoubleMappedBuffer::is_read_only).
     #[allow(clippy::mut_from_ref)]
     pub unsafe fn slice_mut(&self) -> &mut [T] {
-        let addr = self.buffer.addr();
-        debug_assert_eq!(addr % mem::align_of::<T>(), 0);
-        slice::from_raw_parts_mut(addr as *mut T, self.buffer.capacity())
+        debug_assert!(!self.backend.read_only());
+        slice::from_raw_parts_mut(self.addr as *mut T, self.capacity)
     }
 
     /// View of the full buffer, shifted by an offset.
@@ -61,10 +397,24 @@ impl<T> DoubleMappedBuffer<T> {
     /// Provides raw access to the slice. The offset has to be <= the
     /// [capacity](DoubleMappedBuffer::capacity) of the buffer.
     pub unsafe fn slice_with_offset(&self, offset: usize) -> &[T] {
-        let addr = self.buffer.addr();
-        debug_assert_eq!(addr % mem::align_of::<T>(), 0);
-        debug_assert!(offset <= self.buffer.capacity());
-        slice::from_raw_parts((addr as *const T).add(offset), self.buffer.capacity())
+        debug_assert!(offset <= self.capacity);
+        slice::from_raw_parts((self.addr as *const T).add(offset), self.capacity)
+    }
+
+    /// Make `n` items that were written starting at `offset` visible in both
+    /// mappings of the buffer.
+    ///
+    /// Has to be called after writing to the buffer, since the double mapping
+    /// might be [emulated](Backend::mirror).
+    ///
+    /// # Safety
+    ///
+    /// `offset` has to be smaller than and `n` at most the
+    /// [capacity](DoubleMappedBuffer::capacity) of the buffer. The items must
+    /// not be accessed concurrently.
+    pub unsafe fn mirror(&self, offset: usize, n: usize) {
+        let size = mem::size_of::<T>();
+        self.backend.mirror(offset * size, n * size);
     }
 
     /// Mutable view of the full buffer, shifted by an offset.
@@ -72,18 +422,103 @@ impl<T> DoubleMappedBuffer<T> {
     /// # Safety
     ///
     /// Provides raw access to the slice. The offset has to be <= the
-    /// [capacity](DoubleMappedBuffer::capacity) of the buffer.
+    /// [capacity](DoubleMappedBuffer::capacity) of the buffer. The buffer must
+    /// not be [read-only](DoubleMappedBuffer::is_read_only).
+    ///
+    /// Items that are written through the slice have to be
+    /// [mirrored](DoubleMappedBuffer::mirror) before they are read through the
+    /// other mapping. Backends that emulate the double mapping, like the
+    /// [HeapBackend](super::HeapBackend), do not keep the halves consistent
+    /// otherwise.
     #[allow(clippy::mut_from_ref)]
     pub unsafe fn slice_with_offset_mut(&self, offset: usize) -> &mut [T] {
-        let addr = self.buffer.addr();
-        debug_assert_eq!(addr % mem::align_of::<T>(), 0);
-        debug_assert!(offset <= self.buffer.capacity());
-        slice::from_raw_parts_mut((addr as *mut T).add(offset), self.buffer.capacity())
+        debug_assert!(!self.backend.read_only());
+        debug_assert!(offset <= self.capacity);
+        slice::from_raw_parts_mut((self.addr as *mut T).add(offset), self.capacity)
+    }
+
+    /// Safe access to the buffer through [regions](Regions) that are
+    /// borrow-checked at run time.
+    ///
+    /// This is an alternative to the unsafe accessors, which do not prevent
+    /// aliasing mutable slices.
+    pub fn regions(&mut self) -> Regions<'_, T>
+    where
+        T: Pod,
+    {
+        Regions::new(self)
+    }
+
+    /// Mutable view of the full buffer, shifted by an offset, as possibly
+    /// uninitialized items.
+    ///
+    /// # Safety
+    ///
+    /// Provides raw access to the slice. The offset has to be <= the
+    /// [capacity](DoubleMappedBuffer::capacity) of the buffer. The buffer must
+    /// not be [read-only](DoubleMappedBuffer::is_read_only).
+    ///
+    /// Items that are written through the slice have to be
+    /// [mirrored](DoubleMappedBuffer::mirror) before they are read through the
+    /// other mapping. Backends that emulate the double mapping, like the
+    /// [HeapBackend](super::HeapBackend), do not keep the halves consistent
+    /// otherwise.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn slice_with_offset_uninit(&self, offset: usize) -> &mut [MaybeUninit<T>] {
+        debug_assert!(!self.backend.read_only());
+        debug_assert!(offset <= self.capacity);
+        slice::from_raw_parts_mut(
+            (self.addr as *mut MaybeUninit<T>).add(offset),
+            self.capacity,
+        )
     }
 
     /// The capacity of the buffer, i.e., how many items it can hold.
     pub fn capacity(&self) -> usize {
-        self.buffer.capacity()
+        self.capacity
+    }
+
+    /// Label of the buffer in the [accounting](super::accounting).
+    pub fn label(&self) -> Option<String> {
+        self.account.label()
+    }
+
+    /// Set the label of the buffer in the [accounting](super::accounting).
+    pub fn set_label(&self, label: impl Into<String>) {
+        self.account.set_label(Some(label.into()));
+    }
+
+    /// The [Backend] that provides the memory of the buffer.
+    pub fn backend(&self) -> &dyn Backend {
+        self.backend.as_ref()
+    }
+
+    /// Part of the header page of a named buffer that is free for use by the
+    /// buffer implementation, as tuple of address and length.
+    #[cfg(unix)]
+    #[cfg_attr(not(any(feature = "shared", feature = "persistent")), allow(dead_code))]
+    pub(crate) fn user_header(&self) -> Option<(usize, usize)> {
+        self.backend
+            .downcast_ref::<SystemBackend>()
+            .and_then(|b| b.user_header())
+    }
+
+    /// Whether the buffer is mapped read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.backend.read_only()
+    }
+
+    /// The size of the huge pages that back the buffer, or `None` if it is
+    /// backed by regular pages.
+    pub fn huge_pages(&self) -> Option<HugePageSize> {
+        self.backend.huge_pages()
+    }
+
+    /// File descriptor that refers to the memory of the buffer, or `None` if
+    /// the backend does not provide one (see [keep_fd](Options::keep_fd)).
+    #[cfg(unix)]
+    pub fn fd(&self) -> Option<BorrowedFd<'_>> {
+        self.backend.fd()
     }
 }
 
@@ -91,6 +526,12 @@ impl<T> DoubleMappedBuffer<T> {
 mod test {
     use super::*;
     use crate::double_mapped_buffer::pagesize;
+    #[cfg(unix)]
+    use crate::double_mapped_buffer::Backing;
+    use crate::double_mapped_buffer::HeapBackend;
+    use crate::double_mapped_buffer::NumaPolicy;
+    #[cfg(unix)]
+    use std::io::Write;
     use std::mem;
     use std::sync::atomic::compiler_fence;
     use std::sync::atomic::Ordering;
@@ -101,12 +542,12 @@ mod test {
         let ps = pagesize();
 
         assert_eq!(b.capacity() * mem::size_of::<u8>() % ps, 0);
-        assert_eq!(b.buffer.addr() % mem::align_of::<u8>(), 0);
+        assert_eq!(b.backend().addr() % mem::align_of::<u8>(), 0);
 
         unsafe {
             let s = b.slice_mut();
             assert_eq!(s.len(), b.capacity());
-            assert_eq!(s.as_mut_ptr() as usize, b.buffer.addr());
+            assert_eq!(s.as_mut_ptr() as usize, b.backend().addr());
 
             for (i, v) in s.iter_mut().enumerate() {
                 *v = (i % 128) as u8;
@@ -117,7 +558,7 @@ mod test {
             let s = b.slice_with_offset(b.capacity());
             assert_eq!(
                 s.as_ptr() as usize,
-                b.buffer.addr() + b.capacity() * mem::size_of::<u8>()
+                b.backend().addr() + b.capacity() * mem::size_of::<u8>()
             );
             for (i, v) in s.iter().enumerate() {
                 assert_eq!(*v, (i % 128) as u8);
@@ -136,12 +577,12 @@ mod test {
         let ps = pagesize();
 
         assert_eq!(b.capacity() * mem::size_of::<u32>() % ps, 0);
-        assert_eq!(b.buffer.addr() % mem::align_of::<u32>(), 0);
+        assert_eq!(b.backend().addr() % mem::align_of::<u32>(), 0);
 
         unsafe {
             let s = b.slice_mut();
             assert_eq!(s.len(), b.capacity());
-            assert_eq!(s.as_mut_ptr() as usize, b.buffer.addr());
+            assert_eq!(s.as_mut_ptr() as usize, b.backend().addr());
 
             for (i, v) in s.iter_mut().enumerate() {
                 *v = (i % 128) as u32;
@@ -152,7 +593,7 @@ mod test {
             let s = b.slice_with_offset(b.capacity());
             assert_eq!(
                 s.as_ptr() as usize,
-                b.buffer.addr() + b.capacity() * mem::size_of::<u32>()
+                b.backend().addr() + b.capacity() * mem::size_of::<u32>()
             );
             for (i, v) in s.iter().enumerate() {
                 assert_eq!(*v, (i % 128) as u32);
@@ -165,6 +606,354 @@ mod test {
         }
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn backings() {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let backings = [Backing::Auto, Backing::Memfd, Backing::TempFile];
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let backings = [Backing::Auto, Backing::TempFile];
+
+        for backing in backings {
+            let options = Options::new().backing(backing);
+            let b = DoubleMappedBuffer::<u32>::new_in::<SystemBackend>(1234, options)
+                .expect("failed to create buffer");
+            assert!(b.capacity() >= 1234);
+
+            unsafe {
+                b.slice_mut()[12] = 23;
+                compiler_fence(Ordering::SeqCst);
+                assert_eq!(b.slice_with_offset(b.capacity())[12], 23);
+            }
+        }
+    }
+
+    #[test]
+    fn heap_backend() {
+        let b = DoubleMappedBuffer::<u32>::new_in::<HeapBackend>(123, Options::new())
+            .expect("failed to create buffer");
+        let c = b.capacity();
+        assert_eq!(c * mem::size_of::<u32>() % pagesize(), 0);
+
+        unsafe {
+            // write across the wrap point
+            let s = b.slice_with_offset_mut(c - 2);
+            s[..4].copy_from_slice(&[1, 2, 3, 4]);
+            b.mirror(c - 2, 4);
+
+            assert_eq!(&b.slice()[..2], &[3, 4]);
+            assert_eq!(&b.slice()[c - 2..], &[1, 2]);
+            assert_eq!(&b.slice_with_offset(c - 2)[..4], &[1, 2, 3, 4]);
+            assert_eq!(&b.slice_with_offset(c)[..2], &[3, 4]);
+        }
+    }
+
+    #[test]
+    fn heap_backend_mirror() {
+        let b = DoubleMappedBuffer::<u8>::new_in::<HeapBackend>(123, Options::new())
+            .expect("failed to create buffer");
+        assert_eq!(b.backend().addr() % mem::align_of::<u8>(), 0);
+
+        unsafe {
+            let s = b.slice_mut();
+            assert_eq!(s.len(), b.capacity());
+            assert_eq!(s.as_mut_ptr() as usize, b.backend().addr());
+
+            for (i, v) in s.iter_mut().enumerate() {
+                *v = (i % 128) as u8;
+            }
+            b.mirror(0, b.capacity());
+
+            let s = b.slice_with_offset(b.capacity());
+            assert_eq!(
+                s.as_ptr() as usize,
+                b.backend().addr() + b.capacity() * mem::size_of::<u8>()
+            );
+            for (i, v) in s.iter().enumerate() {
+                assert_eq!(*v, (i % 128) as u8);
+            }
+
+            // the other half is only updated through mirror
+            b.slice_mut()[0] = 123;
+            assert_eq!(b.slice_with_offset(b.capacity())[0], 0);
+            b.mirror(0, 1);
+            assert_eq!(b.slice_with_offset(b.capacity())[0], 123);
+        }
+    }
+
+    #[test]
+    fn locked() {
+        let options = Options::new().lock(true);
+        match DoubleMappedBuffer::<u32>::with_options(1234, options) {
+            Ok(b) => unsafe {
+                b.slice_mut()[0] = 42;
+                b.mirror(0, 1);
+                compiler_fence(Ordering::SeqCst);
+                assert_eq!(b.slice_with_offset(b.capacity())[0], 42);
+            },
+            Err(e) => assert!(matches!(
+                e,
+                DoubleMappedBufferError::MemoryLimit(_) | DoubleMappedBufferError::Unsupported
+            )),
+        }
+
+        let options = Options::new().prefault(true);
+        let b = DoubleMappedBuffer::<u32>::with_options(1234, options)
+            .expect("failed to create buffer");
+        assert!(b.capacity() >= 1234);
+    }
+
+    #[test]
+    fn numa() {
+        let options = Options::new().numa(NumaPolicy::Bind(0)).prefault(true);
+        match DoubleMappedBuffer::<u32>::with_options(1234, options) {
+            Ok(b) => assert!(b.capacity() >= 1234),
+            Err(e) => assert!(matches!(
+                e,
+                DoubleMappedBufferError::Numa(_) | DoubleMappedBufferError::Unsupported
+            )),
+        }
+
+        for policy in [NumaPolicy::Bind(4096), NumaPolicy::Interleave(Vec::new())] {
+            let options = Options::new().numa(policy);
+            assert!(DoubleMappedBuffer::<u32>::with_options(1234, options).is_err());
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn advise() {
+        let b = DoubleMappedBuffer::<u32>::new_in::<SystemBackend>(1234, Options::new())
+            .expect("failed to create buffer");
+        b.advise(Advice::DontDump).unwrap();
+
+        unsafe {
+            b.slice_mut()[5] = 42;
+            b.advise(Advice::DontNeed).unwrap();
+            compiler_fence(Ordering::SeqCst);
+            assert_eq!(b.slice()[5], 0);
+            assert_eq!(b.slice_with_offset(b.capacity())[5], 0);
+
+            b.slice_mut()[5] = 23;
+            compiler_fence(Ordering::SeqCst);
+            assert_eq!(b.slice_with_offset(b.capacity())[5], 23);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn advise_file() {
+        let path =
+            std::env::temp_dir().join(format!("vmcircbuffer-advise-file-{}", std::process::id()));
+        let b = DoubleMappedBuffer::<u32>::open_file(&path, 1234, Options::new())
+            .expect("failed to create buffer");
+
+        unsafe {
+            b.slice_mut()[5] = 42;
+            b.advise(Advice::DontNeed).unwrap();
+            compiler_fence(Ordering::SeqCst);
+            // the content of the file is kept
+            assert_eq!(b.slice()[5], 42);
+        }
+        drop(b);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn open_file_limit() {
+        let path =
+            std::env::temp_dir().join(format!("vmcircbuffer-file-limit-{}", std::process::id()));
+        let small = Options::new().max_bytes(1);
+
+        // checked before the file is created
+        let r = DoubleMappedBuffer::<u32>::open_file(&path, 1234, small.clone());
+        assert!(matches!(r, Err(DoubleMappedBufferError::TooLarge { .. })));
+        assert!(!path.exists());
+
+        // an existing file is kept
+        drop(DoubleMappedBuffer::<u32>::open_file(&path, 1234, Options::new()).unwrap());
+        let r = DoubleMappedBuffer::<u32>::open_existing_file(&path, small);
+        assert!(matches!(r, Err(DoubleMappedBufferError::TooLarge { .. })));
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn huge_pages() {
+        // free pages in the pool of 2M huge pages
+        let free =
+            std::fs::read_to_string("/sys/kernel/mm/hugepages/hugepages-2048kB/free_hugepages")
+                .ok()
+                .and_then(|s| s.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+
+        let options = Options::new().huge_pages(HugePageSize::Size2M);
+        match DoubleMappedBuffer::<u32>::with_options(123, options.clone()) {
+            Ok(b) => {
+                assert_eq!(b.huge_pages(), Some(HugePageSize::Size2M));
+                assert_eq!(b.capacity() * mem::size_of::<u32>() % (1 << 21), 0);
+                assert_eq!(b.backend().addr() % (1 << 21), 0);
+            }
+            Err(e) => {
+                assert!(
+                    !cfg!(target_os = "linux") || free == 0,
+                    "huge pages are available, but mapping failed: {e}"
+                );
+                assert!(matches!(e, DoubleMappedBufferError::HugePages(_)));
+                assert!(e.io_error().is_some());
+            }
+        }
+
+        let options =
+            options.huge_page_policy(crate::double_mapped_buffer::HugePagePolicy::Fallback);
+        let b =
+            DoubleMappedBuffer::<u32>::with_options(123, options).expect("failed to create buffer");
+        if b.huge_pages().is_none() {
+            assert_eq!(b.capacity() * mem::size_of::<u32>() % pagesize(), 0);
+        }
+        unsafe {
+            b.slice_mut()[0] = 42;
+            b.mirror(0, 1);
+            compiler_fence(Ordering::SeqCst);
+            assert_eq!(b.slice_with_offset(b.capacity())[0], 42);
+        }
+    }
+
+    #[cfg(all(unix, not(target_os = "android")))]
+    #[test]
+    fn named_buffer() {
+        let name = format!("vmcircbuffer-test-{}", std::process::id());
+        let b = DoubleMappedBuffer::<u32>::create_named(&name, 1234, Options::new())
+            .expect("failed to create buffer");
+        assert!(DoubleMappedBuffer::<u32>::create_named(&name, 1234, Options::new()).is_err());
+
+        let o = DoubleMappedBuffer::<u32>::open_named(&name).expect("failed to open buffer");
+        assert_eq!(o.capacity(), b.capacity());
+        assert_ne!(o.backend().addr(), b.backend().addr());
+
+        unsafe {
+            b.slice_mut()[7] = 42;
+            compiler_fence(Ordering::SeqCst);
+            assert_eq!(o.slice()[7], 42);
+            assert_eq!(o.slice_with_offset(o.capacity())[7], 42);
+        }
+
+        assert!(matches!(
+            DoubleMappedBuffer::<u64>::open_named(&name),
+            Err(DoubleMappedBufferError::Layout)
+        ));
+
+        drop(b);
+        assert!(DoubleMappedBuffer::<u32>::open_named(&name).is_err());
+        unsafe {
+            assert_eq!(o.slice()[7], 42);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn send_receive() {
+        let (tx, rx) = UnixStream::pair().unwrap();
+        let b = DoubleMappedBuffer::<u32>::new_in::<SystemBackend>(1234, Options::new())
+            .expect("failed to create buffer");
+        assert!(b.fd().is_none());
+        assert!(matches!(
+            b.send(&tx),
+            Err(DoubleMappedBufferError::Unsupported)
+        ));
+
+        let options = Options::new().keep_fd(true);
+        let b = DoubleMappedBuffer::<u32>::new_in::<SystemBackend>(1234, options)
+            .expect("failed to create buffer");
+        b.send(&tx).unwrap();
+        b.send(&tx).unwrap();
+
+        let r = DoubleMappedBuffer::<u32>::receive(&rx, Access::ReadOnly).unwrap();
+        let w = DoubleMappedBuffer::<u32>::receive(&rx, Access::ReadWrite).unwrap();
+        assert!(r.is_read_only());
+        assert!(!w.is_read_only());
+        assert_eq!(r.capacity(), b.capacity());
+        assert_eq!(w.capacity(), b.capacity());
+
+        unsafe {
+            w.slice_mut()[3] = 42;
+            compiler_fence(Ordering::SeqCst);
+            assert_eq!(b.slice()[3], 42);
+            assert_eq!(r.slice()[3], 42);
+            assert_eq!(r.slice_with_offset(r.capacity())[3], 42);
+        }
+
+        (&tx).write_all(&[0]).unwrap();
+        assert!(matches!(
+            DoubleMappedBuffer::<u32>::receive(&rx, Access::ReadOnly),
+            Err(DoubleMappedBufferError::Socket(_))
+        ));
+
+        let fd = b.fd().unwrap().try_clone_to_owned().unwrap();
+        assert!(matches!(
+            DoubleMappedBuffer::<[u8; 3]>::from_fd(fd, Access::ReadOnly),
+            Err(DoubleMappedBufferError::Layout)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fd_range() {
+        let ps = pagesize();
+        let options = Options::new().keep_fd(true);
+        let b = DoubleMappedBuffer::<u8>::new_in::<SystemBackend>(3 * ps, options)
+            .expect("failed to create buffer");
+        assert_eq!(b.capacity(), 3 * ps);
+        let fd = || b.fd().unwrap().try_clone_to_owned().unwrap();
+
+        let r = DoubleMappedBuffer::<u8>::from_fd_range(fd(), ps, ps, Access::ReadWrite).unwrap();
+        assert_eq!(r.capacity(), ps);
+        unsafe {
+            b.slice_mut()[ps + 5] = 42;
+            compiler_fence(Ordering::SeqCst);
+            assert_eq!(r.slice()[5], 42);
+            assert_eq!(r.slice_with_offset(ps)[5], 42);
+        }
+
+        assert!(matches!(
+            DoubleMappedBuffer::<u8>::from_fd_range(fd(), 1, ps, Access::ReadWrite),
+            Err(DoubleMappedBufferError::Unaligned)
+        ));
+        assert!(matches!(
+            DoubleMappedBuffer::<u8>::from_fd_range(fd(), 0, ps + 1, Access::ReadWrite),
+            Err(DoubleMappedBufferError::Unaligned)
+        ));
+        assert!(matches!(
+            DoubleMappedBuffer::<[u8; 3]>::from_fd_range(fd(), 0, ps, Access::ReadWrite),
+            Err(DoubleMappedBufferError::Layout)
+        ));
+    }
+
+    #[cfg(all(unix, not(target_os = "android")))]
+    #[test]
+    fn send_receive_named() {
+        let name = format!("vmcircbuffer-test-send-{}", std::process::id());
+        let (tx, rx) = UnixStream::pair().unwrap();
+        let b = DoubleMappedBuffer::<u32>::create_named(&name, 1234, Options::new())
+            .expect("failed to create buffer");
+        b.send(&tx).unwrap();
+        b.send(&tx).unwrap();
+
+        assert!(matches!(
+            DoubleMappedBuffer::<u64>::receive(&rx, Access::ReadOnly),
+            Err(DoubleMappedBufferError::Layout)
+        ));
+        let r = DoubleMappedBuffer::<u32>::receive(&rx, Access::ReadOnly).unwrap();
+        assert_eq!(r.capacity(), b.capacity());
+
+        unsafe {
+            b.slice_mut()[3] = 42;
+            compiler_fence(Ordering::SeqCst);
+            assert_eq!(r.slice()[3], 42);
+        }
+    }
+
     #[test]
     fn many_buffers() {
         let _b0 = DoubleMappedBuffer::<u32>::new(123).expect("failed to create buffer");
@@ -176,4 +965,28 @@ mod test {
             v.push(DoubleMappedBuffer::<u32>::new(123).expect("failed to create buffer"));
         }
     }
+
+    #[test]
+    fn concurrent_buffers() {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..50 {
+                        let b =
+                            DoubleMappedBuffer::<u64>::new(4096).expect("failed to create buffer");
+                        unsafe {
+                            b.slice_mut()[0] = 42;
+                            b.mirror(0, 1);
+                            compiler_fence(Ordering::SeqCst);
+                            assert_eq!(b.slice_with_offset(b.capacity())[0], 42);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for h in handles {
+            h.join().unwrap();
+        }
+    }
 }
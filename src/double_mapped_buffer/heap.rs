@@ -0,0 +1,99 @@
+use std::alloc::{self, Layout};
+use std::any::Any;
+use std::io;
+use std::ptr::{self, NonNull};
+
+use super::Backend;
+use super::DoubleMappedBufferError;
+use super::HugePagePolicy;
+use super::Options;
+
+/// [Backend] that uses a plain heap allocation instead of virtual memory
+/// tricks.
+///
+/// The allocation is twice the size of the buffer. Since the second half is
+/// not mapped to the first half, it is kept in sync by copying
+/// [mirrored](Backend::mirror) ranges into the other half. The circular buffer
+/// implementations of this crate do this, when items are produced. The backend
+/// is, therefore, slower than the [SystemBackend](super::SystemBackend) but
+/// works under Miri and on targets without `mmap`.
+///
+/// Under Miri, it is the [DefaultBackend](super::DefaultBackend). Otherwise, it
+/// has to be selected explicitly, e.g., with
+/// [new_in](super::DoubleMappedBuffer::new_in).
+#[derive(Debug)]
+pub struct HeapBackend {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    size_bytes: usize,
+}
+
+// The allocation is owned by the backend. Concurrent access to the memory is
+// synchronized by the buffer implementations.
+unsafe impl Send for HeapBackend {}
+unsafe impl Sync for HeapBackend {}
+
+unsafe impl Backend for HeapBackend {
+    fn allocate(
+        min_items: usize,
+        item_size: usize,
+        alignment: usize,
+        options: &Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        if options.huge_pages.is_some() && options.huge_page_policy == HugePagePolicy::Require {
+            return Err(DoubleMappedBufferError::HugePages(
+                io::ErrorKind::Unsupported.into(),
+            ));
+        }
+        if options.lock || options.numa.is_some() {
+            return Err(DoubleMappedBufferError::Unsupported);
+        }
+
+        let ps = super::pagesize();
+        let size = super::buffer_size(min_items, item_size, ps)?;
+
+        let layout = Layout::from_size_align(2 * size, alignment.max(ps))
+            .map_err(|_| DoubleMappedBufferError::Alignment)?;
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr)
+            .ok_or_else(|| DoubleMappedBufferError::Create(io::ErrorKind::OutOfMemory.into()))?;
+
+        Ok(HeapBackend {
+            ptr,
+            layout,
+            size_bytes: size,
+        })
+    }
+
+    fn addr(&self) -> usize {
+        self.ptr.as_ptr() as usize
+    }
+
+    fn size_bytes(&self) -> usize {
+        self.size_bytes
+    }
+
+    unsafe fn mirror(&self, offset: usize, len: usize) {
+        debug_assert!(offset < self.size_bytes);
+        debug_assert!(len <= self.size_bytes);
+
+        let base = self.ptr.as_ptr();
+        let size = self.size_bytes;
+        let first = len.min(size - offset);
+
+        // part in the first half is copied to the second half
+        ptr::copy_nonoverlapping(base.add(offset), base.add(size + offset), first);
+        // part that wrapped into the second half is copied to the first half
+        ptr::copy_nonoverlapping(base.add(size), base, len - first);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Drop for HeapBackend {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
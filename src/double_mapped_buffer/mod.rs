@@ -3,48 +3,182 @@
 #[allow(clippy::module_inception)]
 mod double_mapped_buffer;
 pub use double_mapped_buffer::DoubleMappedBuffer;
+pub mod accounting;
+mod backend;
+pub use backend::Backend;
+mod capacity;
+pub(crate) use capacity::buffer_size;
+pub use capacity::plan;
+pub use capacity::Capacity;
+mod heap;
+pub use heap::HeapBackend;
+mod pod;
+#[cfg(feature = "generic")]
+pub(crate) use pod::assume_init_mut;
+pub use pod::Pod;
+mod region;
+pub use region::Region;
+pub use region::RegionError;
+pub use region::RegionMut;
+pub use region::Regions;
+mod options;
+pub use options::Access;
+pub use options::Advice;
+pub use options::Backing;
+pub use options::HugePagePolicy;
+pub use options::HugePageSize;
+pub use options::NumaPolicy;
+pub use options::Options;
 
 #[cfg(windows)]
 mod windows;
 #[cfg(windows)]
-use windows::DoubleMappedBufferImpl;
+pub use windows::SystemBackend;
 
 #[cfg(unix)]
 mod unix;
 #[cfg(unix)]
-use unix::DoubleMappedBufferImpl;
+pub use unix::SystemBackend;
+#[cfg(unix)]
+mod arena;
+#[cfg(unix)]
+pub use arena::Arena;
+#[cfg(unix)]
+pub use arena::ArenaBackend;
 
+/// [Backend] that is used, if none is specified explicitly.
+///
+/// This is the [SystemBackend] or, under Miri and on targets other than Unix
+/// and Windows, the [HeapBackend].
+#[cfg(all(any(unix, windows), not(miri)))]
+pub type DefaultBackend = SystemBackend;
+/// [Backend] that is used, if none is specified explicitly.
+///
+/// This is the `SystemBackend` or, under Miri and on targets other than Unix
+/// and Windows, the [HeapBackend].
+#[cfg(not(all(any(unix, windows), not(miri))))]
+pub type DefaultBackend = HeapBackend;
+
+use std::io;
 use thiserror::Error;
 /// Errors that can occur when setting up the double mapping.
+///
+/// Errors that are caused by a failing system call carry the [io::Error]
+/// with the error code of the operating system.
 #[derive(Error, Debug)]
 pub enum DoubleMappedBufferError {
     /// Failed to close temp file.
-    #[error("Failed to close temp file.")]
-    Close,
+    #[error("Failed to close temp file: {0}")]
+    Close(io::Error),
     /// Failed to unmap second half.
-    #[error("Failed to unmap second half.")]
-    UnmapSecond,
+    #[error("Failed to unmap second half: {0}")]
+    UnmapSecond(io::Error),
     /// Failed to mmap second half.
-    #[error("Failed to mmap second half.")]
-    MapSecond,
+    #[error("Failed to mmap second half: {0}")]
+    MapSecond(io::Error),
     /// Failed to mmap first half.
-    #[error("Failed to mmap first half.")]
-    MapFirst,
+    #[error("Failed to mmap first half: {0}")]
+    MapFirst(io::Error),
     /// Failed to mmap placeholder.
-    #[error("Failed to mmap placeholder.")]
-    Placeholder,
+    #[error("Failed to mmap placeholder: {0}")]
+    Placeholder(io::Error),
     /// Failed to truncate temp file.
-    #[error("Failed to truncate temp file.")]
-    Truncate,
+    #[error("Failed to truncate temp file: {0}")]
+    Truncate(io::Error),
     /// Failed to unlink temp file.
-    #[error("Failed to unlinkt temp file.")]
-    Unlink,
+    #[error("Failed to unlinkt temp file: {0}")]
+    Unlink(io::Error),
     /// Failed to create temp file.
-    #[error("Failed to create temp file.")]
-    Create,
+    #[error("Failed to create temp file: {0}")]
+    Create(io::Error),
+    /// Failed to open named buffer.
+    #[error("Failed to open named buffer: {0}")]
+    Open(io::Error),
+    /// Failed to mmap header of named buffer.
+    #[error("Failed to mmap header: {0}")]
+    MapHeader(io::Error),
+    /// Header of named buffer is invalid.
+    #[error("Invalid buffer header.")]
+    Header,
+    /// Layout of named buffer does not match the item type.
+    #[error("Buffer layout does not match item type.")]
+    Layout,
     /// Wrong alignment for data type.
     #[error("Wrong buffer alignment for data type.")]
     Alignment,
+    /// Offset or length of imported memory is not page aligned.
+    #[error("Offset or length not page aligned.")]
+    Unaligned,
+    /// Failed to send or receive file descriptor.
+    #[error("Failed to send or receive file descriptor: {0}")]
+    Socket(io::Error),
+    /// Huge pages not available.
+    ///
+    /// Carries the error of the operating system or, if the backend does not
+    /// support huge pages, [Unsupported](io::ErrorKind::Unsupported).
+    #[error("Huge pages not available: {0}")]
+    HugePages(io::Error),
+    /// Failed to lock the buffer in RAM.
+    #[error("Failed to lock buffer in RAM: {0}")]
+    Lock(io::Error),
+    /// Locking the buffer exceeds the limit for locked memory (`RLIMIT_MEMLOCK`).
+    #[error("Limit for locked memory exceeded: {0}")]
+    MemoryLimit(io::Error),
+    /// Failed to apply the NUMA policy.
+    #[error("Failed to apply NUMA policy: {0}")]
+    Numa(io::Error),
+    /// Failed to apply memory advice.
+    #[error("Failed to apply memory advice: {0}")]
+    Advise(io::Error),
+    /// Failed to write the buffer back to its file.
+    #[error("Failed to flush buffer: {0}")]
+    Flush(io::Error),
+    /// Buffer would exceed the [budget](accounting::set_budget) of the process.
+    #[error("Memory budget exceeded.")]
+    Budget,
+    /// Arena has no free space for the buffer.
+    #[error("Arena has no space left.")]
+    Exhausted,
+    /// Buffer would be larger than the [limit](Options::max_bytes).
+    #[error("Buffer of {bytes} bytes exceeds limit of {limit} bytes.")]
+    TooLarge {
+        /// Size of the buffer in bytes.
+        bytes: usize,
+        /// Limit in bytes.
+        limit: usize,
+    },
+    /// Size of the buffer overflows.
+    #[error("Buffer size overflows.")]
+    Overflow,
+    /// Requested option is not supported on this platform.
+    #[error("Option not supported on this platform.")]
+    Unsupported,
+}
+
+impl DoubleMappedBufferError {
+    /// The error of the operating system that caused the failure, if any.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            DoubleMappedBufferError::Close(e)
+            | DoubleMappedBufferError::UnmapSecond(e)
+            | DoubleMappedBufferError::MapSecond(e)
+            | DoubleMappedBufferError::MapFirst(e)
+            | DoubleMappedBufferError::Placeholder(e)
+            | DoubleMappedBufferError::Truncate(e)
+            | DoubleMappedBufferError::Unlink(e)
+            | DoubleMappedBufferError::Create(e)
+            | DoubleMappedBufferError::Open(e)
+            | DoubleMappedBufferError::MapHeader(e)
+            | DoubleMappedBufferError::Socket(e)
+            | DoubleMappedBufferError::HugePages(e)
+            | DoubleMappedBufferError::Lock(e)
+            | DoubleMappedBufferError::MemoryLimit(e)
+            | DoubleMappedBufferError::Numa(e)
+            | DoubleMappedBufferError::Advise(e)
+            | DoubleMappedBufferError::Flush(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 // =================== PAGESIZE ======================
@@ -54,7 +188,8 @@ static PAGE_SIZE: OnceCell<usize> = OnceCell::new();
 /// Size of virtual memory pages.
 ///
 /// Determines the granularity of the double buffer, which has to be a multiple
-/// of the page size.
+/// of the page size. Buffers that are backed by huge pages use the
+/// [huge page size](HugePageSize::bytes) instead.
 #[cfg(unix)]
 pub fn pagesize() -> usize {
     *PAGE_SIZE.get_or_init(|| unsafe {
@@ -78,3 +213,8 @@ pub fn pagesize() -> usize {
         info.dwAllocationGranularity as usize
     })
 }
+
+#[cfg(not(any(unix, windows)))]
+pub fn pagesize() -> usize {
+    4096
+}
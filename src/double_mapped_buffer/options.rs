@@ -0,0 +1,225 @@
+/// Kernel object that backs the double mapping.
+///
+/// Only relevant on Unix-based systems. On Windows, buffers are always backed
+/// by the paging file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backing {
+    /// Use an anonymous memory file, if the platform supports it, and fall
+    /// back to a temporary file otherwise.
+    #[default]
+    Auto,
+    /// Anonymous memory file, created with `memfd_create`. This never touches
+    /// the file system. Only available on Linux and Android.
+    Memfd,
+    /// Temporary file, created in [std::env::temp_dir] and unlinked right away.
+    TempFile,
+}
+
+/// Access rights for mapping an existing buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Access {
+    /// Map the buffer readable and writable.
+    #[default]
+    ReadWrite,
+    /// Map the buffer read-only (`PROT_READ`). Any write to the buffer crashes
+    /// the process.
+    ReadOnly,
+}
+
+/// Hint about the use of the buffer memory (`madvise`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Advice {
+    /// Exclude the buffer from core dumps (`MADV_DONTDUMP`).
+    DontDump,
+    /// Back the buffer with transparent huge pages, if possible
+    /// (`MADV_HUGEPAGE`).
+    HugePage,
+    /// Release the pages of the buffer right away. The mapping stays valid, but
+    /// the content of the buffer is lost, unless it is stored in a file on
+    /// disk, which is left untouched.
+    DontNeed,
+    /// Allow the system to release the pages of the buffer, when memory is
+    /// needed. The content of the buffer is lost. Memory that is mapped twice
+    /// is shared memory, for which this is not supported by the kernel, so the
+    /// [SystemBackend](super::SystemBackend) treats it like
+    /// [DontNeed](Advice::DontNeed).
+    Free,
+}
+
+/// Size of huge pages that back the buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HugePageSize {
+    /// 2 MiB pages.
+    Size2M,
+    /// 1 GiB pages.
+    Size1G,
+}
+
+impl HugePageSize {
+    /// Size of the page in bytes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            HugePageSize::Size2M => 1 << 21,
+            HugePageSize::Size1G => 1 << 30,
+        }
+    }
+}
+
+/// What to do if huge pages are not available.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HugePagePolicy {
+    /// Fail with [HugePages](super::DoubleMappedBufferError::HugePages).
+    #[default]
+    Require,
+    /// Fall back to regular pages.
+    Fallback,
+}
+
+/// NUMA memory policy for the pages of the buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NumaPolicy {
+    /// Allocate all pages on the given node.
+    Bind(usize),
+    /// Interleave pages across the given nodes.
+    Interleave(Vec<usize>),
+}
+
+/// Options for setting up a [DoubleMappedBuffer](super::DoubleMappedBuffer).
+///
+/// ```
+/// # use vmcircbuffer::double_mapped_buffer::{Backing, DoubleMappedBuffer, Options};
+/// let options = Options::new().backing(Backing::TempFile);
+/// let buffer = DoubleMappedBuffer::<u32>::with_options(1024, options).unwrap();
+/// assert!(buffer.capacity() >= 1024);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub(crate) backing: Backing,
+    pub(crate) huge_pages: Option<HugePageSize>,
+    pub(crate) huge_page_policy: HugePagePolicy,
+    pub(crate) lock: bool,
+    pub(crate) prefault: bool,
+    pub(crate) numa: Option<NumaPolicy>,
+    pub(crate) label: Option<String>,
+    pub(crate) max_bytes: Option<usize>,
+    pub(crate) keep_fd: bool,
+}
+
+impl Options {
+    /// Default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select the [Backing] of the buffer.
+    pub fn backing(mut self, backing: Backing) -> Self {
+        self.backing = backing;
+        self
+    }
+
+    /// Back the buffer with huge pages of the given size.
+    ///
+    /// The capacity of the buffer is then rounded to a multiple of the huge
+    /// page size. Huge pages are only supported on Linux and Android, where
+    /// they are allocated through `memfd_create` with `MFD_HUGETLB`. They have
+    /// to be reserved by the system administrator (see `vm.nr_hugepages`).
+    pub fn huge_pages(mut self, size: HugePageSize) -> Self {
+        self.huge_pages = Some(size);
+        self
+    }
+
+    /// Select what happens if huge pages were requested but are not available.
+    pub fn huge_page_policy(mut self, policy: HugePagePolicy) -> Self {
+        self.huge_page_policy = policy;
+        self
+    }
+
+    /// Lock the buffer in RAM (`mlock`), so that it is never paged out.
+    ///
+    /// Locking faults in all pages of both mappings. Fails with
+    /// [MemoryLimit](super::DoubleMappedBufferError::MemoryLimit), if the
+    /// buffer exceeds the limit for locked memory of the process (see
+    /// `RLIMIT_MEMLOCK`). Only supported on Unix-based systems.
+    ///
+    /// The pages of locked buffers cannot be released, i.e.,
+    /// [DontNeed](Advice::DontNeed) and [Free](Advice::Free) fail.
+    pub fn lock(mut self, lock: bool) -> Self {
+        self.lock = lock;
+        self
+    }
+
+    /// Populate the page tables of both mappings upfront, so that accessing
+    /// the buffer does not cause page faults.
+    ///
+    /// On Linux and Android, this uses `MADV_POPULATE_WRITE`. Otherwise, every
+    /// page is touched.
+    pub fn prefault(mut self, prefault: bool) -> Self {
+        self.prefault = prefault;
+        self
+    }
+
+    /// Place the pages of the buffer on NUMA nodes according to `policy`.
+    ///
+    /// The policy is applied with `mbind` before the pages are touched, which
+    /// allows to place a buffer next to the thread that consumes it,
+    /// independent of where it is first written. Fails with
+    /// [Numa](super::DoubleMappedBufferError::Numa), if the nodes are not
+    /// available. Only supported on Linux and Android.
+    ///
+    /// ```
+    /// # #[cfg(feature = "sync")] {
+    /// use vmcircbuffer::double_mapped_buffer::{NumaPolicy, Options};
+    /// use vmcircbuffer::sync::Circular;
+    ///
+    /// let options = Options::new().numa(NumaPolicy::Bind(0));
+    /// let writer = Circular::with_options::<f32>(1 << 16, options);
+    /// # }
+    /// ```
+    pub fn numa(mut self, policy: NumaPolicy) -> Self {
+        self.numa = Some(policy);
+        self
+    }
+
+    /// Label the buffer in the [accounting](super::accounting).
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Refuse to create buffers that are larger than `bytes` bytes.
+    ///
+    /// The size of a buffer is rounded up (see [plan](super::plan)). If the
+    /// rounded size exceeds the limit, the buffer is not created and
+    /// [TooLarge](super::DoubleMappedBufferError::TooLarge) is returned.
+    ///
+    /// ```
+    /// use vmcircbuffer::double_mapped_buffer::{DoubleMappedBuffer, Options};
+    ///
+    /// let options = Options::new().max_bytes(1 << 20);
+    /// assert!(DoubleMappedBuffer::<[u8; 4097]>::with_options(1, options).is_err());
+    /// ```
+    pub fn max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Keep the memory file of an anonymous buffer open, so that it can be
+    /// [sent](super::DoubleMappedBuffer::send) to other processes or
+    /// imported with [from_fd](super::DoubleMappedBuffer::from_fd).
+    ///
+    /// Otherwise, the file is closed once the buffer is mapped and
+    /// [fd](super::DoubleMappedBuffer::fd) returns `None`. Named, file-backed,
+    /// and imported buffers always keep their file descriptor. Only supported
+    /// on Unix-based systems.
+    pub fn keep_fd(mut self, keep: bool) -> Self {
+        self.keep_fd = keep;
+        self
+    }
+
+    /// Granularity of the buffer size, i.e., the size of the pages.
+    pub(crate) fn granularity(&self) -> usize {
+        self.huge_pages
+            .map(|h| h.bytes())
+            .unwrap_or_else(super::pagesize)
+    }
+}
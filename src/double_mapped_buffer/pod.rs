@@ -0,0 +1,120 @@
+#[cfg(feature = "generic")]
+use std::mem::MaybeUninit;
+
+/// Marker for plain old data, i.e., types that are valid for every bit pattern.
+///
+/// The memory of a buffer is not initialized through `T`. It is zeroed, when
+/// the buffer is created, or contains whatever was written by another process
+/// or device. Safe APIs that hand out this memory as `T`, are, therefore,
+/// restricted to `Pod` types.
+///
+/// # Safety
+///
+/// Every bit pattern of the size of `T` has to be a valid `T`. This excludes,
+/// for example, `bool`, `char`, enums, references, and pointers with a
+/// non-null invariant. The type must not have padding bytes.
+pub unsafe trait Pod: Copy + Send + Sync + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// View initialized items as `T`.
+///
+/// # Safety
+///
+/// The memory of the items has to be initialized, which holds for memory of a
+/// buffer that was not explicitly de-initialized.
+#[cfg(feature = "generic")]
+pub(crate) unsafe fn assume_init_mut<T: Pod>(items: &mut [MaybeUninit<T>]) -> &mut [T] {
+    // every bit pattern is a valid T
+    &mut *(items as *mut [MaybeUninit<T>] as *mut [T])
+}
+
+/// Define a struct and implement [Pod] for it.
+///
+/// This is the equivalent of a derive for [Pod]. The implementation is checked
+/// at compile time: all fields have to be [Pod] and the struct must not have
+/// padding. The struct has to implement [Copy], which can be derived as usual.
+/// Generic structs are not supported.
+///
+/// ```
+/// use vmcircbuffer::double_mapped_buffer::Pod;
+///
+/// vmcircbuffer::pod! {
+///     /// Complex sample.
+///     #[derive(Clone, Copy, Debug, Default)]
+///     #[repr(C)]
+///     pub struct Complex {
+///         pub re: f32,
+///         pub im: f32,
+///     }
+/// }
+///
+/// vmcircbuffer::pod! {
+///     #[derive(Clone, Copy)]
+///     #[repr(transparent)]
+///     struct Sample(i16);
+/// }
+///
+/// fn is_pod<T: Pod>() {}
+/// is_pod::<Complex>();
+/// is_pod::<Sample>();
+/// ```
+///
+/// Structs with padding are rejected:
+///
+/// ```compile_fail
+/// vmcircbuffer::pod! {
+///     #[derive(Clone, Copy)]
+///     #[repr(C)]
+///     struct Padded {
+///         a: u8,
+///         b: u32,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! pod {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$fattr:meta])* $fvis:vis $field:ident : $fty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($(#[$fattr])* $fvis $field: $fty),*
+        }
+        $crate::pod!(@impl $name, $($fty),*);
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident (
+            $($(#[$fattr:meta])* $fvis:vis $fty:ty),* $(,)?
+        );
+    ) => {
+        $(#[$attr])*
+        $vis struct $name (
+            $($(#[$fattr])* $fvis $fty),*
+        );
+        $crate::pod!(@impl $name, $($fty),*);
+    };
+    (@impl $name:ident, $($fty:ty),*) => {
+        const _: () = {
+            const fn assert_pod<T: $crate::double_mapped_buffer::Pod>() {}
+            $(assert_pod::<$fty>();)*
+            assert!(
+                ::core::mem::size_of::<$name>() == 0 $(+ ::core::mem::size_of::<$fty>())*,
+                "struct has padding"
+            );
+        };
+        unsafe impl $crate::double_mapped_buffer::Pod for $name {}
+    };
+}
@@ -0,0 +1,274 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use thiserror::Error;
+
+use super::DoubleMappedBuffer;
+use super::Pod;
+
+/// Errors borrowing a region of a buffer.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RegionError {
+    /// Offset or length exceed the capacity of the buffer.
+    #[error("Region out of bounds.")]
+    OutOfBounds,
+    /// Region overlaps with a region that is borrowed mutably or, for mutable
+    /// regions, with any borrowed region.
+    #[error("Region already borrowed.")]
+    Borrowed,
+    /// Buffer is mapped read-only.
+    #[error("Buffer is mapped read-only.")]
+    ReadOnly,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Borrow {
+    offset: usize,
+    len: usize,
+    mutable: bool,
+}
+
+/// Safe access to a [DoubleMappedBuffer] through borrowed regions.
+///
+/// A region is described by an offset and a length in items. Like the slices
+/// of the unsafe accessors, it can extend past the end of the buffer into the
+/// second mapping. The regions are borrow-checked at run time, like a
+/// [RefCell](std::cell::RefCell): there can be any number of shared regions or
+/// one mutable region for every item. Since the positions are considered
+/// modulo the capacity, this also covers aliasing through the second mapping.
+///
+/// Created through [DoubleMappedBuffer::regions], which borrows the buffer
+/// mutably. Other buffers that map the same memory, e.g., buffers that are
+/// [opened by name](DoubleMappedBuffer::open_named) or in other processes, are
+/// not considered.
+///
+/// ```
+/// use vmcircbuffer::double_mapped_buffer::{DoubleMappedBuffer, RegionError};
+///
+/// let mut buffer = DoubleMappedBuffer::<f32>::new(1024).unwrap();
+/// let capacity = buffer.capacity();
+/// let regions = buffer.regions();
+///
+/// // wraps around the end of the buffer
+/// let mut a = regions.get_mut(capacity - 10, 20).unwrap();
+/// let mut b = regions.get_mut(10, 100).unwrap();
+/// a.fill(1.0);
+/// b.fill(2.0);
+/// assert!(matches!(regions.get(0, 1), Err(RegionError::Borrowed)));
+///
+/// drop(a);
+/// assert_eq!(regions.get(0, 10).unwrap()[..], [1.0; 10]);
+/// ```
+pub struct Regions<'a, T> {
+    buffer: &'a DoubleMappedBuffer<T>,
+    borrows: Mutex<Vec<Borrow>>,
+}
+
+impl<'a, T: Pod> Regions<'a, T> {
+    pub(super) fn new(buffer: &'a mut DoubleMappedBuffer<T>) -> Self {
+        Regions {
+            buffer,
+            borrows: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Borrow `len` items, starting at `offset`, for reading.
+    ///
+    /// `offset` has to be smaller than and `len` at most the capacity.
+    pub fn get(&self, offset: usize, len: usize) -> Result<Region<'_, 'a, T>, RegionError> {
+        self.borrow(offset, len, false)?;
+        Ok(Region {
+            regions: self,
+            offset,
+            slice: unsafe { &self.buffer.slice_with_offset(offset)[0..len] },
+        })
+    }
+
+    /// Borrow `len` items, starting at `offset`, for writing.
+    ///
+    /// `offset` has to be smaller than and `len` at most the capacity. Written
+    /// items are [mirrored](DoubleMappedBuffer::mirror), when the region is
+    /// dropped.
+    pub fn get_mut(&self, offset: usize, len: usize) -> Result<RegionMut<'_, 'a, T>, RegionError> {
+        if self.buffer.is_read_only() {
+            return Err(RegionError::ReadOnly);
+        }
+        self.borrow(offset, len, true)?;
+        Ok(RegionMut {
+            regions: self,
+            offset,
+            slice: unsafe { &mut self.buffer.slice_with_offset_mut(offset)[0..len] },
+        })
+    }
+
+    /// The capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    fn borrow(&self, offset: usize, len: usize, mutable: bool) -> Result<(), RegionError> {
+        let capacity = self.buffer.capacity();
+        if offset >= capacity || len > capacity {
+            return Err(RegionError::OutOfBounds);
+        }
+
+        let mut borrows = self.borrows.lock().unwrap();
+        if borrows
+            .iter()
+            .any(|b| (mutable || b.mutable) && overlap(capacity, b.offset, b.len, offset, len))
+        {
+            return Err(RegionError::Borrowed);
+        }
+        borrows.push(Borrow {
+            offset,
+            len,
+            mutable,
+        });
+        Ok(())
+    }
+
+    fn release(&self, offset: usize, len: usize, mutable: bool) {
+        let mut borrows = self.borrows.lock().unwrap();
+        let b = Borrow {
+            offset,
+            len,
+            mutable,
+        };
+        if let Some(i) = borrows.iter().position(|x| *x == b) {
+            borrows.swap_remove(i);
+        }
+    }
+}
+
+/// Whether the ranges `[a, a + a_len)` and `[b, b + b_len)` overlap modulo
+/// `capacity`.
+fn overlap(capacity: usize, a: usize, a_len: usize, b: usize, b_len: usize) -> bool {
+    a_len > 0
+        && b_len > 0
+        && ((b + capacity - a) % capacity < a_len || (a + capacity - b) % capacity < b_len)
+}
+
+/// Region of a buffer that is borrowed for reading.
+pub struct Region<'r, 'a, T: Pod> {
+    regions: &'r Regions<'a, T>,
+    offset: usize,
+    slice: &'r [T],
+}
+
+impl<T: Pod> Region<'_, '_, T> {
+    /// Offset of the region in the buffer.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<T: Pod> Deref for Region<'_, '_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.slice
+    }
+}
+
+impl<T: Pod> Drop for Region<'_, '_, T> {
+    fn drop(&mut self) {
+        self.regions.release(self.offset, self.slice.len(), false);
+    }
+}
+
+/// Region of a buffer that is borrowed for writing.
+pub struct RegionMut<'r, 'a, T: Pod> {
+    regions: &'r Regions<'a, T>,
+    offset: usize,
+    slice: &'r mut [T],
+}
+
+impl<T: Pod> RegionMut<'_, '_, T> {
+    /// Offset of the region in the buffer.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<T: Pod> Deref for RegionMut<'_, '_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.slice
+    }
+}
+
+impl<T: Pod> DerefMut for RegionMut<'_, '_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.slice
+    }
+}
+
+impl<T: Pod> Drop for RegionMut<'_, '_, T> {
+    fn drop(&mut self) {
+        let len = self.slice.len();
+        unsafe { self.regions.buffer.mirror(self.offset, len) };
+        self.regions.release(self.offset, len, true);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overlapping() {
+        assert!(overlap(10, 0, 5, 4, 1));
+        assert!(!overlap(10, 0, 5, 5, 5));
+        assert!(overlap(10, 8, 4, 1, 1));
+        assert!(!overlap(10, 8, 4, 2, 6));
+        assert!(overlap(10, 2, 6, 8, 10));
+        assert!(!overlap(10, 3, 0, 0, 10));
+    }
+
+    #[test]
+    fn regions() {
+        let mut b = DoubleMappedBuffer::<u32>::new(1).unwrap();
+        let capacity = b.capacity();
+        let r = b.regions();
+
+        assert_eq!(r.get(capacity, 1).err(), Some(RegionError::OutOfBounds));
+        assert_eq!(r.get(0, capacity + 1).err(), Some(RegionError::OutOfBounds));
+
+        let s1 = r.get(0, 10).unwrap();
+        let s2 = r.get(5, 10).unwrap();
+        assert_eq!(r.get_mut(9, 1).err(), Some(RegionError::Borrowed));
+        drop(s1);
+        drop(s2);
+
+        let mut w = r.get_mut(capacity - 2, 4).unwrap();
+        w.copy_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(r.get(1, 1).err(), Some(RegionError::Borrowed));
+        assert!(r.get(2, capacity - 4).is_ok());
+        drop(w);
+
+        assert_eq!(r.get(0, 2).unwrap()[..], [3, 4]);
+        assert_eq!(r.get(capacity - 2, 2).unwrap()[..], [1, 2]);
+    }
+
+    #[test]
+    fn threads() {
+        let mut b = DoubleMappedBuffer::<u8>::new(1).unwrap();
+        let capacity = b.capacity();
+        let r = b.regions();
+
+        let mut chunks: Vec<_> = (0..4)
+            .map(|i| r.get_mut(i * capacity / 4, capacity / 4).unwrap())
+            .collect();
+        std::thread::scope(|s| {
+            for (i, c) in chunks.iter_mut().enumerate() {
+                s.spawn(move || c.fill(i as u8));
+            }
+        });
+        drop(chunks);
+
+        let all = r.get(0, capacity).unwrap();
+        for (i, c) in all.chunks(capacity / 4).enumerate() {
+            assert!(c.iter().all(|v| *v == i as u8));
+        }
+    }
+}
@@ -1,142 +1,1029 @@
+use std::any::Any;
 use std::ffi::CString;
+use std::io;
 use std::os::unix::ffi::OsStrExt;
-use std::path::PathBuf;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+#[cfg(not(target_os = "android"))]
+use std::path::Path;
+#[cfg(not(target_os = "android"))]
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use super::buffer_size;
 use super::pagesize;
+use super::Access;
+use super::Advice;
+use super::Backend;
+use super::Backing;
 use super::DoubleMappedBufferError;
+use super::HugePagePolicy;
+use super::HugePageSize;
+use super::NumaPolicy;
+use super::Options;
 
+/// [Backend] that double-maps a memory file.
+///
+/// On Linux and Android, the file is usually created with `memfd_create`. On
+/// other Unix-based systems, it is a temporary file (see [Backing]).
 #[derive(Debug)]
-pub struct DoubleMappedBufferImpl {
+pub struct SystemBackend {
     addr: usize,
     size_bytes: usize,
-    item_size: usize,
+    huge_pages: Option<HugePageSize>,
+    header: usize,
+    header_len: usize,
+    read_only: bool,
+    locked: bool,
+    /// Whether the memory is not stored on disk (memfd, POSIX shared memory,
+    /// or an unlinked temporary file), i.e., whether releasing pages may
+    /// remove them from the file.
+    discardable: bool,
+    /// Memory file, unless it was closed after mapping an anonymous buffer.
+    fd: Option<OwnedFd>,
+    #[cfg_attr(target_os = "android", allow(dead_code))]
+    name: Option<CString>,
 }
 
-impl DoubleMappedBufferImpl {
-    pub fn new(
+unsafe impl Backend for SystemBackend {
+    fn allocate(
         min_items: usize,
         item_size: usize,
         alignment: usize,
+        options: &Options,
     ) -> Result<Self, DoubleMappedBufferError> {
-        for _ in 0..5 {
-            let ret = Self::new_try(min_items, item_size, alignment);
-            if ret.is_ok() {
-                return ret;
+        Self::new(min_items, item_size, alignment, options)
+    }
+
+    fn addr(&self) -> usize {
+        self.addr
+    }
+
+    fn size_bytes(&self) -> usize {
+        self.size_bytes
+    }
+
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn huge_pages(&self) -> Option<HugePageSize> {
+        self.huge_pages
+    }
+
+    fn fd(&self) -> Option<BorrowedFd<'_>> {
+        self.fd.as_ref().map(|fd| fd.as_fd())
+    }
+
+    fn advise(&self, advice: Advice) -> Result<(), DoubleMappedBufferError> {
+        let addr = self.addr as *mut libc::c_void;
+        let len = 2 * self.size_bytes;
+
+        // the kernel refuses to release locked pages
+        if self.locked && matches!(advice, Advice::DontNeed | Advice::Free) {
+            return Err(DoubleMappedBufferError::Advise(
+                io::ErrorKind::Unsupported.into(),
+            ));
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let ret = unsafe {
+            match advice {
+                Advice::DontDump => libc::madvise(addr, len, libc::MADV_DONTDUMP),
+                Advice::HugePage => libc::madvise(addr, len, libc::MADV_HUGEPAGE),
+                // removing the pages from the memory file frees them and
+                // unmaps them from both halves, but would punch holes into
+                // files on disk
+                Advice::DontNeed | Advice::Free if self.discardable => {
+                    libc::madvise(addr, self.size_bytes, libc::MADV_REMOVE)
+                }
+                Advice::DontNeed | Advice::Free => libc::madvise(addr, len, libc::MADV_DONTNEED),
+            }
+        };
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let ret = unsafe {
+            match advice {
+                Advice::DontNeed | Advice::Free => libc::madvise(addr, len, libc::MADV_DONTNEED),
+                _ => return Err(DoubleMappedBufferError::Unsupported),
+            }
+        };
+
+        if ret < 0 {
+            return Err(DoubleMappedBufferError::Advise(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    fn flush(&self, wait: bool) -> Result<(), DoubleMappedBufferError> {
+        let flags = if wait { libc::MS_SYNC } else { libc::MS_ASYNC };
+        unsafe {
+            // data first, so that a header on disk never refers to items that
+            // are not on disk yet
+            if libc::msync(self.addr as *mut libc::c_void, self.size_bytes, flags) < 0 {
+                return Err(DoubleMappedBufferError::Flush(io::Error::last_os_error()));
+            }
+            if self.header != 0
+                && libc::msync(self.header as *mut libc::c_void, self.header_len, flags) < 0
+            {
+                return Err(DoubleMappedBufferError::Flush(io::Error::last_os_error()));
             }
         }
-        Self::new_try(min_items, item_size, alignment)
+        Ok(())
     }
 
-    fn new_try(
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl SystemBackend {
+    pub(crate) fn new(
         min_items: usize,
         item_size: usize,
         alignment: usize,
+        options: &Options,
     ) -> Result<Self, DoubleMappedBufferError> {
-        let ps = pagesize();
-        let mut size = ps;
-        while size < min_items * item_size || size % item_size != 0 {
-            size += ps;
-        }
+        let buffer = if options.huge_pages.is_none() {
+            Self::create(min_items, item_size, alignment, options)?
+        } else {
+            match Self::create(min_items, item_size, alignment, options) {
+                Ok(buffer) => buffer,
+                Err(e) => match huge_page_error(e) {
+                    Ok(_) if options.huge_page_policy == HugePagePolicy::Fallback => {
+                        let options = Options {
+                            huge_pages: None,
+                            ..options.clone()
+                        };
+                        Self::create(min_items, item_size, alignment, &options)?
+                    }
+                    Ok(e) => return Err(DoubleMappedBufferError::HugePages(e)),
+                    Err(e) => return Err(e),
+                },
+            }
+        };
+
+        buffer.apply_memory_options(options)
+    }
 
-        let tmp = std::env::temp_dir();
-        let mut path = PathBuf::new();
-        path.push(tmp);
-        path.push("buffer-XXXXXX");
-        let cstring = CString::new(path.into_os_string().as_bytes()).unwrap();
-        let path = cstring.as_bytes_with_nul().as_ptr();
+    /// Apply the NUMA policy, lock the memory, and/or populate the page tables
+    /// of both mappings, if requested through the [Options].
+    fn apply_memory_options(mut self, options: &Options) -> Result<Self, DoubleMappedBufferError> {
+        let addr = self.addr as *mut libc::c_void;
+        let len = 2 * self.size_bytes;
+
+        if let Some(policy) = &options.numa {
+            mbind(self.addr, self.size_bytes, policy)?;
+        }
 
-        let fd;
-        let buff;
         unsafe {
-            fd = libc::mkstemp(path as *mut libc::c_char);
-            if fd < 0 {
-                return Err(DoubleMappedBufferError::Create);
+            if options.lock {
+                if libc::mlock(addr, len) < 0 {
+                    let e = io::Error::last_os_error();
+                    return match e.raw_os_error() {
+                        Some(libc::ENOMEM) | Some(libc::EPERM) => {
+                            Err(DoubleMappedBufferError::MemoryLimit(e))
+                        }
+                        _ => Err(DoubleMappedBufferError::Lock(e)),
+                    };
+                }
+                self.locked = true;
             }
 
-            let ret = libc::unlink(path.cast::<libc::c_char>());
-            if ret < 0 {
-                libc::close(fd);
-                return Err(DoubleMappedBufferError::Unlink);
+            // mlock already faults in all pages
+            if options.prefault && !options.lock {
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                {
+                    let advice = if self.read_only {
+                        libc::MADV_POPULATE_READ
+                    } else {
+                        libc::MADV_POPULATE_WRITE
+                    };
+                    if libc::madvise(addr, len, advice) == 0 {
+                        return Ok(self);
+                    }
+                }
+
+                // fall back to touching every page of both mappings
+                let ps = self.granularity();
+                for offset in (0..len).step_by(ps) {
+                    std::ptr::read_volatile((self.addr + offset) as *const u8);
+                }
             }
+        }
+
+        Ok(self)
+    }
+
+    fn granularity(&self) -> usize {
+        self.huge_pages.map(|h| h.bytes()).unwrap_or_else(pagesize)
+    }
+
+    fn create(
+        min_items: usize,
+        item_size: usize,
+        alignment: usize,
+        options: &Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let size = buffer_size(min_items, item_size, options.granularity())?;
 
-            let ret = libc::ftruncate(fd, 2 * size as libc::off_t);
+        let fd = create_fd(options.backing, options.huge_pages)?;
+        unsafe {
+            let ret = libc::ftruncate(fd.as_raw_fd(), size as libc::off_t);
             if ret < 0 {
-                libc::close(fd);
-                return Err(DoubleMappedBufferError::Truncate);
+                return Err(DoubleMappedBufferError::Truncate(io::Error::last_os_error()));
             }
 
-            buff = libc::mmap(
-                std::ptr::null_mut::<libc::c_void>(),
-                2 * size,
-                libc::PROT_READ | libc::PROT_WRITE,
-                libc::MAP_SHARED,
-                fd,
+            let buff = map_twice(
+                fd.as_raw_fd(),
+                size,
                 0,
+                alignment.max(options.granularity()),
+                Access::ReadWrite,
+            )?;
+
+            Ok(SystemBackend {
+                addr: buff as usize,
+                size_bytes: size,
+                huge_pages: options.huge_pages,
+                header: 0,
+                header_len: 0,
+                read_only: false,
+                locked: false,
+                discardable: true,
+                // the mapping keeps the memory alive, the fd is only needed to share it
+                fd: options.keep_fd.then_some(fd),
+                name: None,
+            })
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    pub(crate) fn create_named(
+        name: &str,
+        min_items: usize,
+        item_size: usize,
+        alignment: usize,
+        options: &Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        // POSIX shared memory is not backed by huge pages
+        if options.huge_pages.is_some() && options.huge_page_policy == HugePagePolicy::Require {
+            return Err(DoubleMappedBufferError::HugePages(
+                io::ErrorKind::Unsupported.into(),
+            ));
+        }
+
+        let name = shm_name(name)?;
+        let header_len = pagesize();
+        let size = buffer_size(min_items, item_size, pagesize())?;
+
+        unsafe {
+            let fd = libc::shm_open(
+                name.as_ptr(),
+                libc::O_RDWR | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC,
+                0o600,
             );
-            if buff == libc::MAP_FAILED {
-                libc::close(fd);
-                return Err(DoubleMappedBufferError::Placeholder);
+            if fd < 0 {
+                return Err(DoubleMappedBufferError::Create(io::Error::last_os_error()));
             }
-            if buff as usize % alignment != 0 {
-                libc::close(fd);
-                return Err(DoubleMappedBufferError::Alignment);
+            let fd = OwnedFd::from_raw_fd(fd);
+
+            match Self::init_header(fd, header_len, size, item_size, alignment) {
+                Ok(mut buffer) => {
+                    buffer.name = Some(name);
+                    buffer.discardable = true;
+                    buffer.apply_memory_options(options)
+                }
+                Err(e) => {
+                    libc::shm_unlink(name.as_ptr());
+                    Err(e)
+                }
             }
+        }
+    }
 
-            let ret = libc::munmap(buff.add(size), size);
-            if ret < 0 {
-                libc::munmap(buff, size);
-                libc::close(fd);
-                return Err(DoubleMappedBufferError::UnmapSecond);
+    /// Open the file at `path` or, if `create` is set, create it, if it does
+    /// not exist. Returns, if the file was created.
+    ///
+    /// Like named buffers, the file starts with a header page.
+    #[cfg(not(target_os = "android"))]
+    pub(crate) fn open_file(
+        path: &Path,
+        create: bool,
+        min_items: usize,
+        item_size: usize,
+        alignment: usize,
+        options: &Options,
+    ) -> Result<(Self, bool), DoubleMappedBufferError> {
+        // page cache of regular files is not backed by huge pages
+        if options.huge_pages.is_some() && options.huge_page_policy == HugePagePolicy::Require {
+            return Err(DoubleMappedBufferError::HugePages(
+                io::ErrorKind::Unsupported.into(),
+            ));
+        }
+
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| DoubleMappedBufferError::Open(e.into()))?;
+
+        unsafe {
+            let fd = if create {
+                libc::open(
+                    path.as_ptr(),
+                    libc::O_RDWR | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC,
+                    0o644 as libc::c_uint,
+                )
+            } else {
+                -1
+            };
+            if fd >= 0 {
+                let fd = OwnedFd::from_raw_fd(fd);
+                let size = buffer_size(min_items, item_size, pagesize())?;
+                return match Self::init_header(fd, pagesize(), size, item_size, alignment)
+                    .and_then(|buffer| buffer.apply_memory_options(options))
+                {
+                    Ok(buffer) => Ok((buffer, true)),
+                    Err(e) => {
+                        libc::unlink(path.as_ptr());
+                        Err(e)
+                    }
+                };
+            }
+            if create {
+                let e = io::Error::last_os_error();
+                if e.raw_os_error() != Some(libc::EEXIST) {
+                    return Err(DoubleMappedBufferError::Create(e));
+                }
             }
 
-            let buff2 = libc::mmap(
-                buff.add(size),
-                size,
-                libc::PROT_READ | libc::PROT_WRITE,
-                libc::MAP_SHARED,
-                fd,
-                0,
-            );
-            if buff2 != buff.add(size) {
-                libc::munmap(buff, size);
-                libc::close(fd);
-                return Err(DoubleMappedBufferError::MapSecond);
+            let fd = libc::open(path.as_ptr(), libc::O_RDWR | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(DoubleMappedBufferError::Open(io::Error::last_os_error()));
             }
+            let fd = OwnedFd::from_raw_fd(fd);
+            let size = file_size(&fd)?;
+            let buffer = Self::with_header(fd, size, item_size, alignment, Access::ReadWrite)?
+                .apply_memory_options(options)?;
+            Ok((buffer, false))
+        }
+    }
 
-            let ret = libc::ftruncate(fd, size as libc::off_t);
-            if ret < 0 {
-                libc::munmap(buff, size);
-                libc::munmap(buff2, size);
-                libc::close(fd);
-                return Err(DoubleMappedBufferError::Truncate);
+    /// Setup a new file with a header page, followed by `size` bytes of data.
+    #[cfg(not(target_os = "android"))]
+    unsafe fn init_header(
+        fd: OwnedFd,
+        header_len: usize,
+        size: usize,
+        item_size: usize,
+        alignment: usize,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let ret = libc::ftruncate(fd.as_raw_fd(), (header_len + size) as libc::off_t);
+        if ret < 0 {
+            return Err(DoubleMappedBufferError::Truncate(io::Error::last_os_error()));
+        }
+
+        let header = map_header(fd.as_raw_fd(), header_len, Access::ReadWrite)?;
+
+        let buff = match map_twice(
+            fd.as_raw_fd(),
+            size,
+            header_len,
+            alignment,
+            Access::ReadWrite,
+        ) {
+            Ok(buff) => buff,
+            Err(e) => {
+                libc::munmap(header, header_len);
+                return Err(e);
             }
+        };
 
-            let ret = libc::close(fd);
-            if ret < 0 {
-                return Err(DoubleMappedBufferError::Close);
+        let h = &*(header as *const Header);
+        h.version.store(VERSION, Ordering::Relaxed);
+        h.item_size.store(item_size as u64, Ordering::Relaxed);
+        h.item_align.store(alignment as u64, Ordering::Relaxed);
+        h.size_bytes.store(size as u64, Ordering::Relaxed);
+        h.header_len.store(header_len as u64, Ordering::Relaxed);
+        h.magic.store(MAGIC, Ordering::Release);
+
+        Ok(SystemBackend {
+            addr: buff as usize,
+            size_bytes: size,
+            huge_pages: None,
+            header: header as usize,
+            header_len,
+            read_only: false,
+            locked: false,
+            discardable: false,
+            fd: Some(fd),
+            name: None,
+        })
+    }
+
+    #[cfg(not(target_os = "android"))]
+    pub(crate) fn open_named(
+        name: &str,
+        item_size: usize,
+        alignment: usize,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let name = shm_name(name)?;
+
+        let fd = unsafe { libc::shm_open(name.as_ptr(), libc::O_RDWR | libc::O_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(DoubleMappedBufferError::Open(io::Error::last_os_error()));
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let size = file_size(&fd)?;
+        let mut buffer = Self::with_header(fd, size, item_size, alignment, Access::ReadWrite)?;
+        buffer.discardable = true;
+        Ok(buffer)
+    }
+
+    /// Map a buffer with a header page, i.e., a named buffer.
+    #[cfg(not(target_os = "android"))]
+    fn with_header(
+        fd: OwnedFd,
+        file_size: usize,
+        item_size: usize,
+        alignment: usize,
+        access: Access,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let header_len = pagesize();
+        if file_size < header_len {
+            return Err(DoubleMappedBufferError::Header);
+        }
+
+        unsafe {
+            let header = map_header(fd.as_raw_fd(), header_len, access)?;
+            let size = match check_header(header, header_len, file_size, item_size, alignment) {
+                Ok(size) => size,
+                Err(e) => {
+                    libc::munmap(header, header_len);
+                    return Err(e);
+                }
+            };
+
+            let buff = match map_twice(fd.as_raw_fd(), size, header_len, alignment, access) {
+                Ok(buff) => buff,
+                Err(e) => {
+                    libc::munmap(header, header_len);
+                    return Err(e);
+                }
+            };
+
+            Ok(SystemBackend {
+                addr: buff as usize,
+                size_bytes: size,
+                huge_pages: None,
+                header: header as usize,
+                header_len,
+                read_only: access == Access::ReadOnly,
+                locked: false,
+                discardable: false,
+                fd: Some(fd),
+                name: None,
+            })
+        }
+    }
+
+    pub(crate) fn from_fd(
+        fd: OwnedFd,
+        item_size: usize,
+        alignment: usize,
+        access: Access,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        let size = file_size(&fd)?;
+
+        // buffers that were created with a name start with a header page
+        #[cfg(not(target_os = "android"))]
+        if size > pagesize() {
+            let header = unsafe { map_header(fd.as_raw_fd(), pagesize(), Access::ReadOnly)? };
+            let ret = unsafe { check_header(header, pagesize(), size, item_size, alignment) };
+            unsafe {
+                libc::munmap(header, pagesize());
             }
+            match ret {
+                Ok(_) => return Self::with_header(fd, size, item_size, alignment, access),
+                Err(DoubleMappedBufferError::Layout) => {
+                    return Err(DoubleMappedBufferError::Layout)
+                }
+                Err(_) => {}
+            }
+        }
+
+        if size == 0 || !size.is_multiple_of(pagesize()) || !size.is_multiple_of(item_size) {
+            return Err(DoubleMappedBufferError::Layout);
         }
 
-        Ok(DoubleMappedBufferImpl {
+        let buff = unsafe { map_twice(fd.as_raw_fd(), size, 0, alignment, access)? };
+
+        Ok(SystemBackend {
             addr: buff as usize,
             size_bytes: size,
-            item_size,
+            huge_pages: None,
+            header: 0,
+            header_len: 0,
+            read_only: access == Access::ReadOnly,
+            locked: false,
+            discardable: false,
+            fd: Some(fd),
+            name: None,
         })
     }
 
-    pub fn addr(&self) -> usize {
-        self.addr
+    pub(crate) fn from_fd_range(
+        fd: OwnedFd,
+        offset: usize,
+        len: usize,
+        item_size: usize,
+        alignment: usize,
+        access: Access,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        if len == 0 || !offset.is_multiple_of(pagesize()) || !len.is_multiple_of(pagesize()) {
+            return Err(DoubleMappedBufferError::Unaligned);
+        }
+        if !len.is_multiple_of(item_size) {
+            return Err(DoubleMappedBufferError::Layout);
+        }
+
+        let buff = unsafe { map_twice(fd.as_raw_fd(), len, offset, alignment, access)? };
+
+        Ok(SystemBackend {
+            addr: buff as usize,
+            size_bytes: len,
+            huge_pages: None,
+            header: 0,
+            header_len: 0,
+            read_only: access == Access::ReadOnly,
+            locked: false,
+            discardable: false,
+            fd: Some(fd),
+            name: None,
+        })
+    }
+
+    #[cfg(not(target_os = "android"))]
+    pub(crate) fn unlink_named(name: &str) -> Result<(), DoubleMappedBufferError> {
+        let name = shm_name(name)?;
+        let ret = unsafe { libc::shm_unlink(name.as_ptr()) };
+        if ret < 0 {
+            return Err(DoubleMappedBufferError::Unlink(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    /// Part of the header page of a named buffer that is not used to describe
+    /// the layout, as tuple of address and length.
+    pub(crate) fn user_header(&self) -> Option<(usize, usize)> {
+        if self.header == 0 {
+            return None;
+        }
+        Some((
+            self.header + HEADER_RESERVED,
+            self.header_len - HEADER_RESERVED,
+        ))
+    }
+}
+
+/// Apply the NUMA `policy` to `len` bytes, starting at `addr`.
+///
+/// Pages that are already allocated are moved. For memory files, the policy is
+/// attached to the file, i.e., it also applies to the second mapping.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn mbind(addr: usize, len: usize, policy: &NumaPolicy) -> Result<(), DoubleMappedBufferError> {
+    // linux/mempolicy.h
+    const MPOL_BIND: libc::c_long = 2;
+    const MPOL_INTERLEAVE: libc::c_long = 3;
+    const MPOL_MF_MOVE: libc::c_long = 1 << 1;
+
+    let bits = libc::c_ulong::BITS as usize;
+    let (mode, nodes) = match policy {
+        NumaPolicy::Bind(node) => (MPOL_BIND, std::slice::from_ref(node)),
+        NumaPolicy::Interleave(nodes) => (MPOL_INTERLEAVE, nodes.as_slice()),
+    };
+    let max = nodes
+        .iter()
+        .copied()
+        .max()
+        .ok_or_else(|| DoubleMappedBufferError::Numa(io::ErrorKind::InvalidInput.into()))?;
+    let mut mask = vec![0 as libc::c_ulong; max / bits + 1];
+    for node in nodes {
+        mask[node / bits] |= 1 << (node % bits);
+    }
+
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            addr,
+            len,
+            mode,
+            mask.as_ptr(),
+            // the kernel considers one bit less than passed
+            mask.len() * bits + 1,
+            MPOL_MF_MOVE,
+        )
+    };
+    if ret < 0 {
+        return Err(DoubleMappedBufferError::Numa(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn mbind(_addr: usize, _len: usize, _policy: &NumaPolicy) -> Result<(), DoubleMappedBufferError> {
+    Err(DoubleMappedBufferError::Unsupported)
+}
+
+/// Errors of creating a buffer with huge pages that indicate that huge pages
+/// are not available, as `Ok` with the error of the operating system.
+fn huge_page_error(e: DoubleMappedBufferError) -> Result<io::Error, DoubleMappedBufferError> {
+    match e {
+        DoubleMappedBufferError::Create(e)
+        | DoubleMappedBufferError::Truncate(e)
+        | DoubleMappedBufferError::MapFirst(e)
+        | DoubleMappedBufferError::MapSecond(e) => Ok(e),
+        e => Err(e),
+    }
+}
+
+/// Map `size` bytes of `fd`, starting at `offset`, twice, back-to-back.
+///
+/// The address range for both mappings is reserved with a `PROT_NONE` mapping
+/// first. The file is then mapped with `MAP_FIXED` on top of this reservation,
+/// which atomically replaces the reserved pages. Since the reservation is owned
+/// by us, other threads cannot grab parts of the address range in between.
+///
+/// The mappings start at a multiple of `alignment`. Files with huge pages can
+/// only be mapped at addresses that are aligned to the huge page size, while
+/// the reservation is only aligned to the page size. For larger alignments,
+/// the reservation is, therefore, increased by `alignment` and trimmed to an
+/// aligned range.
+unsafe fn map_twice(
+    fd: libc::c_int,
+    size: usize,
+    offset: usize,
+    alignment: usize,
+    access: Access,
+) -> Result<*mut libc::c_void, DoubleMappedBufferError> {
+    let extra = if alignment > pagesize() { alignment } else { 0 };
+    let reserved = libc::mmap(
+        std::ptr::null_mut::<libc::c_void>(),
+        2 * size + extra,
+        libc::PROT_NONE,
+        libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    if reserved == libc::MAP_FAILED {
+        return Err(DoubleMappedBufferError::Placeholder(
+            io::Error::last_os_error(),
+        ));
+    }
+
+    let head = (reserved as usize).next_multiple_of(alignment) - reserved as usize;
+    if head > 0 {
+        libc::munmap(reserved, head);
+    }
+    if extra > head {
+        libc::munmap(reserved.add(head + 2 * size), extra - head);
+    }
+    let buff = reserved.add(head);
+
+    let buff1 = libc::mmap(
+        buff,
+        size,
+        access.prot(),
+        libc::MAP_SHARED | libc::MAP_FIXED,
+        fd,
+        offset as libc::off_t,
+    );
+    if buff1 != buff {
+        let e = io::Error::last_os_error();
+        libc::munmap(buff, 2 * size);
+        return Err(DoubleMappedBufferError::MapFirst(e));
+    }
+
+    let buff2 = libc::mmap(
+        buff.add(size),
+        size,
+        access.prot(),
+        libc::MAP_SHARED | libc::MAP_FIXED,
+        fd,
+        offset as libc::off_t,
+    );
+    if buff2 != buff.add(size) {
+        let e = io::Error::last_os_error();
+        libc::munmap(buff, 2 * size);
+        return Err(DoubleMappedBufferError::MapSecond(e));
+    }
+
+    Ok(buff)
+}
+
+// =================== NAMED ======================
+// Named buffers start with a header page that describes the layout of the
+// buffer. The data follows at an offset of one page.
+#[cfg(not(target_os = "android"))]
+const MAGIC: u64 = u64::from_le_bytes(*b"VMCIRCBF");
+#[cfg(not(target_os = "android"))]
+const VERSION: u64 = 1;
+/// Bytes of the header page that are reserved for the [Header].
+const HEADER_RESERVED: usize = 128;
+
+#[cfg(not(target_os = "android"))]
+#[repr(C)]
+struct Header {
+    magic: AtomicU64,
+    version: AtomicU64,
+    item_size: AtomicU64,
+    item_align: AtomicU64,
+    size_bytes: AtomicU64,
+    header_len: AtomicU64,
+}
+
+#[cfg(not(target_os = "android"))]
+const _: () = assert!(std::mem::size_of::<Header>() <= HEADER_RESERVED);
+
+#[cfg(not(target_os = "android"))]
+fn shm_name(name: &str) -> Result<CString, DoubleMappedBufferError> {
+    let name = if name.starts_with('/') {
+        name.to_string()
+    } else {
+        format!("/{name}")
+    };
+    CString::new(name).map_err(|e| DoubleMappedBufferError::Create(e.into()))
+}
+
+#[cfg(not(target_os = "android"))]
+unsafe fn map_header(
+    fd: libc::c_int,
+    header_len: usize,
+    access: Access,
+) -> Result<*mut libc::c_void, DoubleMappedBufferError> {
+    let header = libc::mmap(
+        std::ptr::null_mut::<libc::c_void>(),
+        header_len,
+        access.prot(),
+        libc::MAP_SHARED,
+        fd,
+        0,
+    );
+    if header == libc::MAP_FAILED {
+        return Err(DoubleMappedBufferError::MapHeader(
+            io::Error::last_os_error(),
+        ));
+    }
+    Ok(header)
+}
+
+/// Validate the header and return the size of the buffer in bytes.
+#[cfg(not(target_os = "android"))]
+unsafe fn check_header(
+    header: *mut libc::c_void,
+    header_len: usize,
+    file_size: usize,
+    item_size: usize,
+    alignment: usize,
+) -> Result<usize, DoubleMappedBufferError> {
+    let h = &*(header as *const Header);
+    let size = h.size_bytes.load(Ordering::Relaxed) as usize;
+    if h.magic.load(Ordering::Acquire) != MAGIC
+        || h.version.load(Ordering::Relaxed) != VERSION
+        || h.header_len.load(Ordering::Relaxed) as usize != header_len
+        || header_len + size != file_size
+    {
+        Err(DoubleMappedBufferError::Header)
+    } else if h.item_size.load(Ordering::Relaxed) as usize != item_size
+        || h.item_align.load(Ordering::Relaxed) as usize != alignment
+    {
+        Err(DoubleMappedBufferError::Layout)
+    } else {
+        Ok(size)
+    }
+}
+
+impl Access {
+    fn prot(&self) -> libc::c_int {
+        match self {
+            Access::ReadWrite => libc::PROT_READ | libc::PROT_WRITE,
+            Access::ReadOnly => libc::PROT_READ,
+        }
+    }
+}
+
+fn file_size(fd: &OwnedFd) -> Result<usize, DoubleMappedBufferError> {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) };
+    if ret < 0 {
+        return Err(DoubleMappedBufferError::Open(io::Error::last_os_error()));
+    }
+    Ok(stat.st_size as usize)
+}
+
+// =================== FD PASSING ======================
+/// Size of the control message that carries one file descriptor.
+const CMSG_BUFFER_LEN: usize =
+    unsafe { libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as u32) } as usize;
+
+/// Buffer for the control message, aligned for [libc::cmsghdr].
+#[repr(C, align(8))]
+struct CmsgBuffer([u8; CMSG_BUFFER_LEN]);
+
+const _: () = assert!(std::mem::align_of::<CmsgBuffer>() >= std::mem::align_of::<libc::cmsghdr>());
+
+/// Send `fd` over a Unix domain socket with `SCM_RIGHTS`.
+pub fn send_fd(socket: BorrowedFd<'_>, fd: BorrowedFd<'_>) -> Result<(), DoubleMappedBufferError> {
+    unsafe {
+        let mut data = [0u8; 1];
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        };
+        let mut cmsg = CmsgBuffer([0; CMSG_BUFFER_LEN]);
+
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg.0.as_mut_ptr().cast();
+        msg.msg_controllen = CMSG_BUFFER_LEN as _;
+
+        let hdr = libc::CMSG_FIRSTHDR(&msg);
+        (*hdr).cmsg_level = libc::SOL_SOCKET;
+        (*hdr).cmsg_type = libc::SCM_RIGHTS;
+        (*hdr).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<libc::c_int>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(hdr).cast::<libc::c_int>(), fd.as_raw_fd());
+
+        let ret = libc::sendmsg(socket.as_raw_fd(), &msg, 0);
+        if ret < 0 {
+            return Err(DoubleMappedBufferError::Socket(io::Error::last_os_error()));
+        }
+    }
+    Ok(())
+}
+
+/// Receive a file descriptor from a Unix domain socket.
+pub fn recv_fd(socket: BorrowedFd<'_>) -> Result<OwnedFd, DoubleMappedBufferError> {
+    unsafe {
+        let mut data = [0u8; 1];
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        };
+        let mut cmsg = CmsgBuffer([0; CMSG_BUFFER_LEN]);
+
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg.0.as_mut_ptr().cast();
+        msg.msg_controllen = CMSG_BUFFER_LEN as _;
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let flags = libc::MSG_CMSG_CLOEXEC;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let flags = 0;
+
+        let ret = libc::recvmsg(socket.as_raw_fd(), &mut msg, flags);
+        if ret < 0 {
+            return Err(DoubleMappedBufferError::Socket(io::Error::last_os_error()));
+        }
+        if ret == 0 {
+            return Err(DoubleMappedBufferError::Socket(
+                io::ErrorKind::UnexpectedEof.into(),
+            ));
+        }
+
+        let hdr = libc::CMSG_FIRSTHDR(&msg);
+        if hdr.is_null() {
+            return Err(DoubleMappedBufferError::Socket(
+                io::ErrorKind::InvalidData.into(),
+            ));
+        }
+        // take ownership of all received descriptors, before validating
+        let payload = ((*hdr).cmsg_len as usize)
+            .saturating_sub(libc::CMSG_LEN(0) as usize)
+            .min(CMSG_BUFFER_LEN);
+        let fds: Vec<OwnedFd> = if (*hdr).cmsg_level == libc::SOL_SOCKET
+            && (*hdr).cmsg_type == libc::SCM_RIGHTS
+        {
+            (0..payload / std::mem::size_of::<libc::c_int>())
+                .map(|i| {
+                    let fd =
+                        std::ptr::read_unaligned(libc::CMSG_DATA(hdr).cast::<libc::c_int>().add(i));
+                    OwnedFd::from_raw_fd(fd)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        if msg.msg_flags & libc::MSG_CTRUNC != 0
+            || (*hdr).cmsg_len as usize
+                != libc::CMSG_LEN(std::mem::size_of::<libc::c_int>() as u32) as usize
+            || fds.len() != 1
+        {
+            return Err(DoubleMappedBufferError::Socket(
+                io::ErrorKind::InvalidData.into(),
+            ));
+        }
+        Ok(fds.into_iter().next().unwrap())
+    }
+}
+
+/// Create the file descriptor that backs the mapping.
+pub(super) fn create_fd(
+    backing: Backing,
+    huge_pages: Option<HugePageSize>,
+) -> Result<OwnedFd, DoubleMappedBufferError> {
+    match (backing, huge_pages) {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        (Backing::Auto, None) => match memfd(0) {
+            // kernels before 3.17 do not know memfd_create
+            Err(DoubleMappedBufferError::Create(e)) if e.raw_os_error() == Some(libc::ENOSYS) => {
+                temp_file()
+            }
+            ret => ret,
+        },
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        (Backing::Auto, None) => temp_file(),
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        (Backing::Memfd, None) => memfd(0),
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        (Backing::Auto | Backing::Memfd, Some(HugePageSize::Size2M)) => {
+            memfd(libc::MFD_HUGETLB | libc::MFD_HUGE_2MB)
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        (Backing::Auto | Backing::Memfd, Some(HugePageSize::Size1G)) => {
+            memfd(libc::MFD_HUGETLB | libc::MFD_HUGE_1GB)
+        }
+        (Backing::TempFile, None) => temp_file(),
+        _ => Err(DoubleMappedBufferError::Unsupported),
     }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn memfd(flags: libc::c_uint) -> Result<OwnedFd, DoubleMappedBufferError> {
+    let fd = unsafe { libc::memfd_create(c"vmcircbuffer".as_ptr(), libc::MFD_CLOEXEC | flags) };
+    if fd < 0 {
+        return Err(DoubleMappedBufferError::Create(io::Error::last_os_error()));
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
 
-    pub fn capacity(&self) -> usize {
-        self.size_bytes / self.item_size
+fn temp_file() -> Result<OwnedFd, DoubleMappedBufferError> {
+    let mut path = std::env::temp_dir();
+    path.push("buffer-XXXXXX");
+    let mut path = CString::new(path.into_os_string().as_bytes())
+        .map_err(|e| DoubleMappedBufferError::Create(e.into()))?
+        .into_bytes_with_nul();
+    let path = path.as_mut_ptr().cast::<libc::c_char>();
+
+    unsafe {
+        let fd = libc::mkstemp(path);
+        if fd < 0 {
+            return Err(DoubleMappedBufferError::Create(io::Error::last_os_error()));
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+
+        let ret = libc::unlink(path);
+        if ret < 0 {
+            return Err(DoubleMappedBufferError::Unlink(io::Error::last_os_error()));
+        }
+        Ok(fd)
     }
 }
 
-impl Drop for DoubleMappedBufferImpl {
+impl Drop for SystemBackend {
     fn drop(&mut self) {
         unsafe {
+            if self.locked {
+                libc::munlock(self.addr as *mut libc::c_void, self.size_bytes * 2);
+            }
             libc::munmap(self.addr as *mut libc::c_void, self.size_bytes * 2);
+            if self.header != 0 {
+                libc::munmap(self.header as *mut libc::c_void, self.header_len);
+            }
+            #[cfg(not(target_os = "android"))]
+            if let Some(name) = &self.name {
+                libc::shm_unlink(name.as_ptr());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn map_twice_aligned() {
+        let size = pagesize();
+        let alignment = 1 << 21;
+        let fd = create_fd(Backing::Auto, None).unwrap();
+        unsafe {
+            assert_eq!(libc::ftruncate(fd.as_raw_fd(), size as libc::off_t), 0);
+            let buff = map_twice(fd.as_raw_fd(), size, 0, alignment, Access::ReadWrite).unwrap();
+            assert_eq!(buff as usize % alignment, 0);
+
+            *buff.cast::<u8>() = 42;
+            assert_eq!(std::ptr::read_volatile(buff.cast::<u8>().add(size)), 42);
+            libc::munmap(buff, 2 * size);
         }
     }
 }
@@ -16,30 +16,80 @@ use winapi::um::{
     winbase::CreateFileMappingA,
 };
 
+use std::any::Any;
+use std::io;
+
+use super::buffer_size;
 use super::pagesize;
+use super::Backend;
 use super::DoubleMappedBufferError;
+use super::HugePagePolicy;
+use super::Options;
 
+/// [Backend] that maps a section of the paging file twice.
 #[derive(Debug)]
-pub struct DoubleMappedBufferImpl {
+pub struct SystemBackend {
     addr: usize,
     handle: usize,
     size_bytes: usize,
-    item_size: usize,
 }
 
-impl DoubleMappedBufferImpl {
-    pub fn new(
+unsafe impl Backend for SystemBackend {
+    fn allocate(
+        min_items: usize,
+        item_size: usize,
+        alignment: usize,
+        options: &Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        Self::new(min_items, item_size, alignment, options)
+    }
+
+    fn addr(&self) -> usize {
+        self.addr
+    }
+
+    fn size_bytes(&self) -> usize {
+        self.size_bytes
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl SystemBackend {
+    pub(crate) fn new(
         min_items: usize,
         item_size: usize,
         alignment: usize,
+        options: &Options,
     ) -> Result<Self, DoubleMappedBufferError> {
+        // large pages require SeLockMemoryPrivilege and are not supported
+        if options.huge_pages.is_some() && options.huge_page_policy == HugePagePolicy::Require {
+            return Err(DoubleMappedBufferError::HugePages(
+                io::ErrorKind::Unsupported.into(),
+            ));
+        }
+        if options.lock || options.numa.is_some() {
+            return Err(DoubleMappedBufferError::Unsupported);
+        }
+
+        let mut ret = Self::new_try(min_items, item_size, alignment);
         for _ in 0..5 {
-            let ret = Self::new_try(min_items, item_size, alignment);
             if ret.is_ok() {
-                return ret;
+                break;
+            }
+            ret = Self::new_try(min_items, item_size, alignment);
+        }
+        let buffer = ret?;
+
+        if options.prefault {
+            for offset in (0..2 * buffer.size_bytes).step_by(pagesize()) {
+                unsafe { std::ptr::read_volatile((buffer.addr + offset) as *const u8) };
             }
         }
-        Self::new_try(min_items, item_size, alignment)
+
+        Ok(buffer)
     }
 
     fn new_try(
@@ -47,11 +97,7 @@ impl DoubleMappedBufferImpl {
         item_size: usize,
         alignment: usize,
     ) -> Result<Self, DoubleMappedBufferError> {
-        let ps = pagesize();
-        let mut size = ps;
-        while size < min_items * item_size || size % item_size != 0 {
-            size += ps;
-        }
+        let size = buffer_size(min_items, item_size, pagesize())?;
 
         unsafe {
             let handle = CreateFileMappingA(
@@ -64,26 +110,31 @@ impl DoubleMappedBufferImpl {
             );
 
             if handle == INVALID_HANDLE_VALUE || handle == 0 as LPVOID {
-                return Err(DoubleMappedBufferError::Placeholder);
+                return Err(DoubleMappedBufferError::Placeholder(
+                    io::Error::last_os_error(),
+                ));
             }
 
             let first_tmp =
                 VirtualAlloc(std::ptr::null_mut(), 2 * size, MEM_RESERVE, PAGE_NOACCESS);
             if first_tmp.is_null() {
+                let e = io::Error::last_os_error();
                 CloseHandle(handle);
-                return Err(DoubleMappedBufferError::MapFirst);
+                return Err(DoubleMappedBufferError::MapFirst(e));
             }
 
             let res = VirtualFree(first_tmp, 0, MEM_RELEASE);
             if res == 0 {
+                let e = io::Error::last_os_error();
                 CloseHandle(handle);
-                return Err(DoubleMappedBufferError::MapSecond);
+                return Err(DoubleMappedBufferError::MapSecond(e));
             }
 
             let first_cpy = MapViewOfFileEx(handle, FILE_MAP_WRITE, 0, 0, size, first_tmp);
             if first_tmp != first_cpy {
+                let e = io::Error::last_os_error();
                 CloseHandle(handle);
-                return Err(DoubleMappedBufferError::MapFirst);
+                return Err(DoubleMappedBufferError::MapFirst(e));
             }
 
             if first_tmp as usize % alignment != 0 {
@@ -94,30 +145,22 @@ impl DoubleMappedBufferImpl {
             let first_ptr = (first_tmp as *mut u8).add(size) as LPVOID;
             let second_cpy = MapViewOfFileEx(handle, FILE_MAP_WRITE, 0, 0, size, first_ptr);
             if second_cpy != first_ptr {
+                let e = io::Error::last_os_error();
                 UnmapViewOfFile(first_cpy);
                 CloseHandle(handle);
-                return Err(DoubleMappedBufferError::MapSecond);
+                return Err(DoubleMappedBufferError::MapSecond(e));
             }
 
-            Ok(DoubleMappedBufferImpl {
+            Ok(SystemBackend {
                 addr: first_tmp as usize,
                 handle: handle as usize,
                 size_bytes: size,
-                item_size,
             })
         }
     }
-
-    pub fn addr(&self) -> usize {
-        self.addr
-    }
-
-    pub fn capacity(&self) -> usize {
-        self.size_bytes / self.item_size
-    }
 }
 
-impl Drop for DoubleMappedBufferImpl {
+impl Drop for SystemBackend {
     fn drop(&mut self) {
         unsafe {
             UnmapViewOfFile(self.addr as LPCVOID);
@@ -0,0 +1,51 @@
+//! Open file description locks on single bytes of a buffer file.
+//!
+//! The kernel releases the locks, when the last file descriptor of the open
+//! file description is closed, i.e., at the latest, when the process
+//! terminates. Contrary to PIDs, this is independent of PID namespaces, PID
+//! reuse, and reboots. The shared and persistent buffers use them to detect
+//! peers that terminated without detaching.
+
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, BorrowedFd};
+
+fn file_lock(ty: libc::c_int, byte: usize) -> libc::flock {
+    let mut fl: libc::flock = unsafe { mem::zeroed() };
+    fl.l_type = ty as libc::c_short;
+    fl.l_whence = libc::SEEK_SET as libc::c_short;
+    fl.l_start = byte as libc::off_t;
+    fl.l_len = 1;
+    fl
+}
+
+/// Try to lock `byte` of the file with an open file description lock.
+///
+/// Returns `false`, if the byte is locked through another open file
+/// description.
+pub(crate) fn try_lock(fd: BorrowedFd<'_>, byte: usize) -> io::Result<bool> {
+    let fl = file_lock(libc::F_WRLCK, byte);
+    if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_OFD_SETLK, &fl) } == 0 {
+        return Ok(true);
+    }
+    let e = io::Error::last_os_error();
+    match e.raw_os_error() {
+        Some(libc::EAGAIN) | Some(libc::EACCES) => Ok(false),
+        _ => Err(e),
+    }
+}
+
+pub(crate) fn unlock(fd: BorrowedFd<'_>, byte: usize) {
+    let fl = file_lock(libc::F_UNLCK, byte);
+    unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_OFD_SETLK, &fl) };
+}
+
+/// Whether `byte` of the file is locked through another open file
+/// description, i.e., whether its owner is alive.
+///
+/// Errors are treated as locked, to not free state of live processes.
+pub(crate) fn locked(fd: BorrowedFd<'_>, byte: usize) -> bool {
+    let mut fl = file_lock(libc::F_WRLCK, byte);
+    let ret = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_OFD_GETLK, &mut fl) };
+    ret != 0 || fl.l_type != libc::F_UNLCK as libc::c_short
+}
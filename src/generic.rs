@@ -1,17 +1,85 @@
 //! Circular Buffer with generic [Notifier] to implement custom wait/block behavior.
 
+use once_cell::sync::OnceCell;
 use slab::Slab;
-use std::sync::{Arc, Mutex};
+use std::mem::MaybeUninit;
+use std::ops::Deref;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+use crate::double_mapped_buffer::assume_init_mut;
+use crate::double_mapped_buffer::buffer_size;
+use crate::double_mapped_buffer::Advice;
+use crate::double_mapped_buffer::Backend;
+use crate::double_mapped_buffer::DefaultBackend;
 use crate::double_mapped_buffer::DoubleMappedBuffer;
+use crate::double_mapped_buffer::DoubleMappedBufferError;
+use crate::double_mapped_buffer::Options;
+use crate::double_mapped_buffer::Pod;
+
+mod batch;
+mod multi;
+mod owned;
+pub use batch::{Chunk, WriteBatch};
+pub use multi::{MultiWriter, Reservation};
+pub use owned::{Drain, OwnedReader, OwnedWriter};
 
 /// Error setting up the underlying buffer.
 #[derive(Error, Debug)]
 pub enum CircularError {
     /// Failed to allocate double mapped buffer.
-    #[error("Failed to allocate double mapped buffer.")]
-    Allocation,
+    ///
+    /// The `requested` size is the number of requested items times the item
+    /// size. The `rounded` size is the size of the buffer after rounding it to
+    /// the page and item size, saturated at `usize::MAX`. Both are in bytes.
+    #[error("Failed to allocate double mapped buffer of {rounded} bytes ({requested} bytes requested): {error}")]
+    Allocation {
+        /// Error of the underlying buffer.
+        error: DoubleMappedBufferError,
+        /// Requested size in bytes.
+        requested: usize,
+        /// Rounded size in bytes.
+        rounded: usize,
+    },
+    /// Buffer is mapped read-only.
+    #[error("Buffer is mapped read-only.")]
+    ReadOnly,
+    /// Failed to apply memory advice.
+    #[error("Failed to apply memory advice.")]
+    Advise,
+    /// Buffer holds items that were not read by all readers.
+    #[error("Buffer is not empty.")]
+    NotEmpty,
+    /// Buffer was not allocated by the circular buffer and cannot be resized.
+    #[error("Buffer cannot be resized.")]
+    Resize,
+    /// Buffer would exceed the memory [budget](crate::double_mapped_buffer::accounting::set_budget).
+    #[error("Memory budget exceeded.")]
+    Budget,
+    /// Buffer already has readers.
+    #[error("Buffer already has readers.")]
+    Readers,
+}
+
+impl CircularError {
+    pub(crate) fn allocation<T>(
+        error: DoubleMappedBufferError,
+        min_items: usize,
+        options: &Options,
+    ) -> Self {
+        if let DoubleMappedBufferError::Budget = error {
+            return CircularError::Budget;
+        }
+        let item_size = std::mem::size_of::<T>();
+        CircularError::Allocation {
+            error,
+            requested: min_items.saturating_mul(item_size),
+            rounded: buffer_size(min_items, item_size, options.granularity()).unwrap_or(usize::MAX),
+        }
+    }
 }
 
 /// A custom notifier can be used to trigger arbitrary mechanism to signal to a
@@ -31,6 +99,11 @@ pub trait Notifier {
 pub trait Metadata {
     type Item: Clone;
 
+    /// Whether the metadata is void, i.e., all functions are no-ops, like for
+    /// [NoMetadata]. Void metadata is not synchronized between the writer and
+    /// the readers.
+    const VOID: bool = false;
+
     /// Create metadata container.
     fn new() -> Self;
     /// Add metadata, applying `offset` shift to items.
@@ -45,6 +118,7 @@ pub trait Metadata {
 pub struct NoMetadata;
 impl Metadata for NoMetadata {
     type Item = ();
+    const VOID: bool = true;
 
     fn new() -> Self {
         Self
@@ -68,44 +142,329 @@ impl Circular {
         N: Notifier,
         M: Metadata,
     {
-        let buffer = match DoubleMappedBuffer::new(min_items) {
-            Ok(buffer) => Arc::new(buffer),
-            Err(_) => return Err(CircularError::Allocation),
-        };
+        Self::with_options(min_items, Options::default())
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with the underlying [DoubleMappedBuffer] configured through [Options].
+    pub fn with_options<T, N, M>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<Writer<T, N, M>, CircularError>
+    where
+        N: Notifier,
+        M: Metadata,
+    {
+        Self::with_backend::<T, DefaultBackend, N, M>(min_items, options)
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with memory that is provided by [Backend] `B`.
+    pub fn with_backend<T, B, N, M>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<Writer<T, N, M>, CircularError>
+    where
+        B: Backend,
+        N: Notifier,
+        M: Metadata,
+    {
+        let buffer = DoubleMappedBuffer::new_in::<B>(min_items, options.clone())
+            .map_err(|e| CircularError::allocation::<T>(e, min_items, &options))?;
 
-        let state = Arc::new(Mutex::new(State {
-            writer_offset: 0,
-            writer_ab: false,
-            writer_done: false,
-            readers: Slab::new(),
-        }));
+        let mut writer = Self::with_buffer(buffer)?;
+        writer.allocate = Some((DoubleMappedBuffer::new_in::<B>, options));
+        Ok(writer)
+    }
+
+    /// Create a circular buffer on top of an existing [DoubleMappedBuffer].
+    ///
+    /// This allows, for example, to use a buffer that was
+    /// [imported](DoubleMappedBuffer::from_fd_range) from a device driver.
+    pub fn with_buffer<T, N, M>(
+        buffer: DoubleMappedBuffer<T>,
+    ) -> Result<Writer<T, N, M>, CircularError>
+    where
+        N: Notifier,
+        M: Metadata,
+    {
+        if buffer.is_read_only() {
+            return Err(CircularError::ReadOnly);
+        }
+        let buffer = Arc::new(buffer);
+
+        let state = Arc::new(State {
+            writer: CachePadded(AtomicU64::new(0)),
+            writer_done: AtomicBool::new(false),
+            writer_slice: AtomicU8::new(IDLE),
+            buffer: Mutex::new(buffer.clone()),
+            buffer_generation: AtomicUsize::new(0),
+            readers: Mutex::new(Slab::new()),
+            readers_generation: AtomicUsize::new(0),
+            created: Instant::now(),
+            last_produce: AtomicU64::new(0),
+            reclaim: Mutex::new(Reclaim {
+                after: None,
+                registered: false,
+            }),
+            reclaimed: AtomicBool::new(false),
+            owned: OnceCell::new(),
+        });
 
         let writer = Writer {
+            position: 0,
+            last_space: 0,
+            blocked: 0,
+            grow: None,
+            allocate: None,
+            reclaim: false,
+            readers: Vec::new(),
+            readers_generation: 0,
             buffer,
             state,
-            last_space: 0,
         };
 
         Ok(writer)
     }
 }
 
-struct State<N, M>
+/// Pads and aligns a value to two cache lines, which also avoids false
+/// sharing through adjacent-line prefetching.
+#[repr(align(128))]
+pub(crate) struct CachePadded<T>(pub(crate) T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// The writer neither holds a slice nor is the buffer reclaimed.
+const IDLE: u8 = 0;
+/// The writer might hold a slice to write to the buffer.
+const SLICE: u8 = 1;
+/// The buffer is reclaimed by the background thread.
+const RECLAIM: u8 = 2;
+
+/// State that is shared between the writer and the readers.
+///
+/// The writer and the readers keep their positions as monotonic counters of
+/// items. The offset in the buffer is the position modulo the capacity. Since
+/// everybody only updates their own position, producing and consuming items
+/// does not lock. Only adding and removing readers and resizing the buffer
+/// synchronize.
+struct State<T, N, M>
 where
     N: Notifier,
     M: Metadata,
 {
-    writer_offset: usize,
-    writer_ab: bool,
-    writer_done: bool,
-    readers: Slab<ReaderState<N, M>>,
+    /// Position of the writer.
+    writer: CachePadded<AtomicU64>,
+    writer_done: AtomicBool,
+    /// Whether the writer holds a slice or the buffer is reclaimed.
+    writer_slice: AtomicU8,
+    /// The current buffer, which changes, when the writer resizes it.
+    buffer: Mutex<Arc<DoubleMappedBuffer<T>>>,
+    /// Incremented, when the buffer changes.
+    buffer_generation: AtomicUsize,
+    readers: Mutex<Slab<Arc<ReaderState<N, M>>>>,
+    /// Incremented, when a reader is added or removed.
+    readers_generation: AtomicUsize,
+    created: Instant,
+    /// Time of the last produce in nanoseconds since `created`. Only updated,
+    /// if the buffer is reclaimed automatically.
+    last_produce: AtomicU64,
+    reclaim: Mutex<Reclaim>,
+    reclaimed: AtomicBool,
+    /// Reader of an owned buffer, which moves items out. Items that are not
+    /// read are dropped.
+    owned: OnceCell<Arc<ReaderState<N, M>>>,
 }
+
+struct Reclaim {
+    after: Option<Duration>,
+    registered: bool,
+}
+
+impl<T, N, M> State<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    /// Whether all readers read all items.
+    fn empty(&self) -> bool {
+        let writer = self.writer.load(Ordering::Acquire);
+        self.readers
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|(_, r)| r.position.load(Ordering::Acquire) == writer)
+    }
+
+    /// Nanoseconds since the buffer was created.
+    fn now(&self) -> u64 {
+        self.created.elapsed().as_nanos() as u64
+    }
+}
+
+impl<T, N, M> Drop for State<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    fn drop(&mut self) {
+        if let Some(reader) = self.owned.get() {
+            let writer = *self.writer.0.get_mut();
+            let position = reader.position.load(Ordering::Acquire);
+            let buffer = self.buffer.get_mut().unwrap();
+            unsafe { drop_items(buffer, position, (writer - position) as usize) };
+        }
+    }
+}
+
+/// Drop `n` items, starting at `position`.
+///
+/// # Safety
+///
+/// The items have to be initialized and must not be used afterwards.
+unsafe fn drop_items<T>(buffer: &DoubleMappedBuffer<T>, position: u64, n: usize) {
+    let offset = (position % buffer.capacity() as u64) as usize;
+    let items = &mut buffer.slice_with_offset_uninit(offset)[0..n];
+    ptr::drop_in_place(items as *mut [MaybeUninit<T>] as *mut [T]);
+}
+
+/// How often the reclaim thread checks for idle buffers.
+const RECLAIM_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Buffers for which [reclaim_after](Writer::reclaim_after) was set.
+static IDLE_BUFFERS: OnceCell<Mutex<Vec<Box<dyn IdleBuffer>>>> = OnceCell::new();
+
+trait IdleBuffer: Send {
+    /// Release the pages of the buffer, if it is idle.
+    ///
+    /// Returns `false`, if the buffer was dropped.
+    fn reclaim(&self) -> bool;
+}
+
+struct WeakBuffer<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    state: Weak<State<T, N, M>>,
+}
+
+impl<T, N, M> IdleBuffer for WeakBuffer<T, N, M>
+where
+    T: Send + Sync,
+    N: Notifier + Send,
+    M: Metadata + Send,
+{
+    fn reclaim(&self) -> bool {
+        let Some(state) = self.state.upgrade() else {
+            return false;
+        };
+        let mut reclaim = state.reclaim.lock().unwrap();
+
+        let idle = match reclaim.after {
+            Some(idle) => {
+                let last = state.last_produce.load(Ordering::Relaxed);
+                Duration::from_nanos(state.now().saturating_sub(last)) >= idle
+            }
+            None => {
+                reclaim.registered = false;
+                return false;
+            }
+        };
+        if idle
+            && !state.reclaimed.load(Ordering::Relaxed)
+            && state
+                .writer_slice
+                .compare_exchange(IDLE, RECLAIM, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        {
+            let mut supported = true;
+            if state.empty() {
+                // fails for locked buffers and backends without support
+                supported = state
+                    .buffer
+                    .lock()
+                    .unwrap()
+                    .advise(Advice::DontNeed)
+                    .is_ok();
+                state.reclaimed.store(true, Ordering::Relaxed);
+            }
+            state.writer_slice.store(IDLE, Ordering::Release);
+            if !supported {
+                // this does not change, so stop checking the buffer
+                reclaim.after = None;
+                reclaim.registered = false;
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn register_idle_buffer(buffer: Box<dyn IdleBuffer>) {
+    let mut init = false;
+    let buffers = IDLE_BUFFERS.get_or_init(|| {
+        init = true;
+        Mutex::new(Vec::new())
+    });
+    buffers.lock().unwrap().push(buffer);
+
+    if init {
+        std::thread::spawn(move || loop {
+            std::thread::sleep(RECLAIM_INTERVAL);
+            buffers.lock().unwrap().retain(|b| b.reclaim());
+        });
+    }
+}
+
 struct ReaderState<N, M> {
-    ab: bool,
-    offset: usize,
-    reader_notifier: N,
-    writer_notifier: N,
-    meta: M,
+    /// Position of the reader.
+    position: CachePadded<AtomicU64>,
+    reader_notifier: Notify<N>,
+    writer_notifier: Notify<N>,
+    /// Only locked, if the metadata is not [void](Metadata::VOID).
+    meta: Mutex<M>,
+}
+
+/// [Notifier] with a flag to check, without locking, whether it is armed.
+pub(crate) struct Notify<N> {
+    armed: AtomicBool,
+    notifier: Mutex<N>,
+}
+
+impl<N: Notifier> Notify<N> {
+    pub(crate) fn new(notifier: N) -> Self {
+        Notify {
+            armed: AtomicBool::new(false),
+            notifier: Mutex::new(notifier),
+        }
+    }
+
+    /// Arm the notifier.
+    ///
+    /// The caller has to check the condition it is waiting for again, after
+    /// arming the notifier.
+    pub(crate) fn arm(&self) {
+        self.notifier.lock().unwrap().arm();
+        self.armed.store(true, Ordering::SeqCst);
+    }
+
+    /// Notify, if armed.
+    ///
+    /// The caller has to update the condition the other side is waiting for
+    /// before.
+    pub(crate) fn notify(&self) {
+        if self.armed.load(Ordering::SeqCst) && self.armed.swap(false, Ordering::SeqCst) {
+            self.notifier.lock().unwrap().notify();
+        }
+    }
 }
 
 /// Writer for a generic circular buffer with items of type `T` and [Notifier] of type `N`.
@@ -114,9 +473,53 @@ where
     N: Notifier,
     M: Metadata,
 {
+    /// Position of the writer, which is only updated by the writer.
+    position: u64,
     last_space: usize,
+    /// Number of times the writer found the buffer full since it last grew.
+    blocked: usize,
+    grow: Option<GrowPolicy>,
+    allocate: Option<(Allocate<T>, Options)>,
+    /// The buffer might be reclaimed by the background thread.
+    reclaim: bool,
+    /// Snapshot of the readers.
+    readers: Vec<Arc<ReaderState<N, M>>>,
+    readers_generation: usize,
     buffer: Arc<DoubleMappedBuffer<T>>,
-    state: Arc<Mutex<State<N, M>>>,
+    state: Arc<State<T, N, M>>,
+}
+
+type Allocate<T> = fn(usize, Options) -> Result<DoubleMappedBuffer<T>, DoubleMappedBufferError>;
+
+/// Policy to grow the buffer, when the writer keeps finding it full.
+///
+/// ```
+/// # use vmcircbuffer::generic::GrowPolicy;
+/// let policy = GrowPolicy::new(1 << 20).blocked(4);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GrowPolicy {
+    max_items: usize,
+    blocked: usize,
+}
+
+impl GrowPolicy {
+    /// Double the capacity of the buffer, until it can hold at least
+    /// `max_items` items.
+    pub fn new(max_items: usize) -> Self {
+        GrowPolicy {
+            max_items,
+            blocked: 16,
+        }
+    }
+
+    /// Grow the buffer, once the writer found it full `blocked` times.
+    ///
+    /// The default is 16.
+    pub fn blocked(mut self, blocked: usize) -> Self {
+        self.blocked = blocked;
+        self
+    }
 }
 
 impl<T, N, M> Writer<T, N, M>
@@ -126,65 +529,134 @@ where
 {
     /// Add a [Reader] to the buffer.
     pub fn add_reader(&self, reader_notifier: N, writer_notifier: N) -> Reader<T, N, M> {
-        let mut state = self.state.lock().unwrap();
-        let reader_state = ReaderState {
-            ab: state.writer_ab,
-            offset: state.writer_offset,
-            reader_notifier,
-            writer_notifier,
-            meta: M::new(),
-        };
-        let id = state.readers.insert(reader_state);
+        let reader = Arc::new(ReaderState {
+            position: CachePadded(AtomicU64::new(self.position)),
+            reader_notifier: Notify::new(reader_notifier),
+            writer_notifier: Notify::new(writer_notifier),
+            meta: Mutex::new(M::new()),
+        });
+
+        let mut readers = self.state.readers.lock().unwrap();
+        let id = readers.insert(reader.clone());
+        self.state
+            .readers_generation
+            .fetch_add(1, Ordering::Release);
 
         Reader {
             id,
+            position: self.position,
             last_space: 0,
+            reader,
             buffer: self.buffer.clone(),
+            buffer_generation: self.state.buffer_generation.load(Ordering::Acquire),
             state: self.state.clone(),
         }
     }
 
-    fn space_and_offset(&self, arm: bool) -> (usize, usize) {
-        let mut state = self.state.lock().unwrap();
-        let capacity = self.buffer.capacity();
-        let w_off = state.writer_offset;
-        let w_ab = state.writer_ab;
-
-        let mut space = capacity;
-
-        for (_, reader) in state.readers.iter_mut() {
-            let r_off = reader.offset;
-            let r_ab = reader.ab;
+    /// Update the snapshot of the readers, if readers were added or removed.
+    fn update_readers(&mut self) {
+        if self.state.readers_generation.load(Ordering::SeqCst) != self.readers_generation {
+            let readers = self.state.readers.lock().unwrap();
+            self.readers = readers.iter().map(|(_, r)| r.clone()).collect();
+            self.readers_generation = self.state.readers_generation.load(Ordering::Relaxed);
+        }
+    }
 
-            let s = if w_off > r_off {
-                r_off + capacity - w_off
-            } else if w_off < r_off {
-                r_off - w_off
-            } else if r_ab == w_ab {
-                capacity
-            } else {
-                0
-            };
+    /// Mark that the writer might hold a slice, waiting for the reclaim
+    /// thread, if it releases the pages of the buffer right now.
+    fn busy(&self) {
+        if self.reclaim {
+            while let Err(RECLAIM) = self.state.writer_slice.compare_exchange(
+                IDLE,
+                SLICE,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                std::thread::yield_now();
+            }
+        }
+    }
 
-            space = std::cmp::min(space, s);
+    /// Mark that the writer does not hold a slice.
+    fn idle(&self) {
+        if self.reclaim {
+            self.state.writer_slice.store(IDLE, Ordering::Release);
+        }
+    }
 
-            if s == 0 && arm {
-                reader.writer_notifier.arm();
-                break;
+    /// Free space and the index of the slowest reader.
+    fn min_space(&self) -> (usize, Option<usize>) {
+        let capacity = self.buffer.capacity();
+        let mut space = (capacity, None);
+        for (i, reader) in self.readers.iter().enumerate() {
+            let unread = self.position - reader.position.load(Ordering::SeqCst);
+            let s = capacity - unread as usize;
+            if s < space.0 || space.1.is_none() {
+                space = (s, Some(i));
             }
             if s == 0 {
                 break;
             }
         }
+        space
+    }
 
-        (space, w_off)
+    fn space_and_offset(&mut self, arm: bool) -> (usize, usize) {
+        self.space_and_offset_for(1, arm)
+    }
+
+    /// Free space and offset of the writer. If less than `needed` items are
+    /// free and `arm` is set, the notifier of the slowest reader is armed.
+    fn space_and_offset_for(&mut self, needed: usize, arm: bool) -> (usize, usize) {
+        self.busy();
+        self.update_readers();
+
+        let mut space = self.min_space();
+        if let (s, Some(i)) = space {
+            if s < needed && arm {
+                self.readers[i].writer_notifier.arm();
+                self.update_readers();
+                space = self.min_space();
+            }
+        }
+
+        let offset = (self.position % self.buffer.capacity() as u64) as usize;
+        (space.0, offset)
     }
 
     /// Get a slice for the output buffer space. Might be empty.
-    pub fn slice(&mut self, arm: bool) -> &mut [T] {
-        let (space, offset) = self.space_and_offset(arm);
+    ///
+    /// The slice holds old items or, if nothing was written yet, zeros. This is
+    /// only safe for [Pod] types. Other types have to use
+    /// [slice_uninit](Writer::slice_uninit).
+    ///
+    /// If the buffer is full and a [GrowPolicy] is set, the buffer might be
+    /// [resized](Writer::resize).
+    pub fn slice(&mut self, arm: bool) -> &mut [T]
+    where
+        T: Pod,
+    {
+        unsafe { assume_init_mut(self.slice_uninit(arm)) }
+    }
+
+    /// Get a slice for the output buffer space as possibly uninitialized
+    /// items. Might be empty.
+    ///
+    /// Written items are handed to the readers with
+    /// [assume_init](Writer::assume_init).
+    ///
+    /// # Safety
+    ///
+    /// Items must not be de-initialized, i.e., only initialized values may be
+    /// written to the slice. For [Pod] types, the memory is later handed out
+    /// as initialized items.
+    pub unsafe fn slice_uninit(&mut self, arm: bool) -> &mut [MaybeUninit<T>] {
+        let (mut space, mut offset) = self.space_and_offset(arm);
+        if space == 0 && self.grow() {
+            (space, offset) = self.space_and_offset(arm);
+        }
         self.last_space = space;
-        unsafe { &mut self.buffer.slice_with_offset_mut(offset)[0..space] }
+        &mut self.buffer.slice_with_offset_uninit(offset)[0..space]
     }
 
     /// Indicates that `n` items were written to the output buffer.
@@ -194,7 +666,31 @@ where
     /// # Panics
     ///
     /// If produced more than space was available in the last provided slice.
-    pub fn produce(&mut self, n: usize, meta: Vec<M::Item>) {
+    pub fn produce(&mut self, n: usize, meta: Vec<M::Item>)
+    where
+        T: Pod,
+    {
+        unsafe { self.assume_init(n, meta) }
+    }
+
+    /// Indicates that the first `n` items of the last provided slice were
+    /// initialized and hands them to the readers, like [produce](Writer::produce).
+    ///
+    /// # Safety
+    ///
+    /// The first `n` items of the last provided slice have to be initialized.
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    pub unsafe fn assume_init(&mut self, n: usize, meta: Vec<M::Item>) {
+        unsafe { self.publish(n, meta) };
+        self.idle();
+    }
+
+    /// Hand `n` initialized items to the readers, without releasing the
+    /// slice, i.e., the writer stays busy.
+    unsafe fn publish(&mut self, n: usize, meta: Vec<M::Item>) {
         if n == 0 {
             return;
         }
@@ -204,34 +700,194 @@ where
         assert!(n <= self.last_space, "vmcircbuffer: produced too much");
         self.last_space -= n;
 
-        let mut state = self.state.lock().unwrap();
+        self.update_readers();
+        let position = self.position + n as u64;
 
-        let w_off = state.writer_offset;
-        let w_ab = state.writer_ab;
-        let capacity = self.buffer.capacity();
+        if let Some(owned) = self.state.owned.get() {
+            if self.readers.is_empty() {
+                // the reader is gone and nobody will take the items
+                let start = owned.position.load(Ordering::Acquire);
+                unsafe { drop_items(&self.buffer, start, (position - start) as usize) };
+                owned.position.store(position, Ordering::Release);
+                self.state.writer.store(position, Ordering::Release);
+                self.position = position;
+                return;
+            }
+        }
+
+        let offset = (self.position % self.buffer.capacity() as u64) as usize;
+        unsafe { self.buffer.mirror(offset, n) };
+
+        if self.reclaim {
+            self.state
+                .last_produce
+                .store(self.state.now(), Ordering::Relaxed);
+            self.state.reclaimed.store(false, Ordering::Relaxed);
+        }
 
-        for (_, r) in state.readers.iter_mut() {
-            let r_off = r.offset;
-            let r_ab = r.ab;
-
-            let space = if r_off > w_off {
-                w_off + capacity - r_off
-            } else if r_off < w_off {
-                w_off - r_off
-            } else if r_ab == w_ab {
-                0
-            } else {
-                capacity
-            };
-
-            r.meta.add(space, meta.clone());
+        if M::VOID {
+            self.state.writer.store(position, Ordering::SeqCst);
+        } else {
+            // tags and items become visible to a reader at the same time
+            let mut guards: Vec<_> = self
+                .readers
+                .iter()
+                .map(|r| r.meta.lock().unwrap())
+                .collect();
+            for (r, m) in self.readers.iter().zip(guards.iter_mut()) {
+                let unread = self.position - r.position.load(Ordering::Acquire);
+                m.add(unread as usize, meta.clone());
+            }
+            self.state.writer.store(position, Ordering::SeqCst);
+        }
+        self.position = position;
+
+        for r in self.readers.iter() {
             r.reader_notifier.notify();
         }
+    }
+
+    /// Apply a hint about the use of the buffer memory (`madvise`).
+    ///
+    /// [DontNeed](Advice::DontNeed) and [Free](Advice::Free) release the
+    /// pages of the buffer and are, therefore, only applied, if all readers
+    /// read all items. Otherwise, [NotEmpty](CircularError::NotEmpty) is
+    /// returned. Buffers that are [locked](Options::lock) in RAM cannot be
+    /// released, for them, [Advise](CircularError::Advise) is returned.
+    pub fn advise(&mut self, advice: Advice) -> Result<(), CircularError> {
+        self.idle();
+
+        if matches!(advice, Advice::DontNeed | Advice::Free) && !self.state.empty() {
+            return Err(CircularError::NotEmpty);
+        }
+        self.buffer
+            .advise(advice)
+            .map_err(|_| CircularError::Advise)
+    }
+
+    /// Release the pages of the buffer automatically, once all readers read
+    /// all items and nothing was produced for the `idle` duration.
+    ///
+    /// Idle buffers are checked by a background thread in intervals of
+    /// 100ms. The buffer is not reclaimed, while the writer holds a slice of
+    /// the output buffer, i.e., after [slice](Writer::slice) was called and
+    /// before the items were [produced](Writer::produce). `None` disables
+    /// automatic reclaim.
+    ///
+    /// Buffers that are [locked](Options::lock) in RAM or whose [Backend]
+    /// cannot release pages are never reclaimed. Automatic reclaim is
+    /// disabled for them, once they are idle for the first time.
+    pub fn reclaim_after(&mut self, idle: Option<Duration>)
+    where
+        T: Send + Sync + 'static,
+        N: Send + 'static,
+        M: Send + 'static,
+    {
+        if idle.is_some() && !self.reclaim {
+            self.reclaim = true;
+            self.state.writer_slice.store(
+                if self.last_space > 0 { SLICE } else { IDLE },
+                Ordering::Release,
+            );
+        }
+        self.state
+            .last_produce
+            .store(self.state.now(), Ordering::Relaxed);
+
+        let mut reclaim = self.state.reclaim.lock().unwrap();
+        let register = idle.is_some() && !reclaim.registered;
+        reclaim.after = idle;
+        reclaim.registered |= register;
+        drop(reclaim);
+
+        if register {
+            register_idle_buffer(Box::new(WeakBuffer {
+                state: Arc::downgrade(&self.state),
+            }));
+        }
+    }
+
+    /// Resize the buffer, such that it can hold at least `min_items` items.
+    ///
+    /// A new buffer is allocated with the same [Backend] and [Options] and
+    /// items that were not read by all readers are copied. The buffer is
+    /// never shrunk below the number of unread items. Readers stay valid and
+    /// continue with the next unread item. They switch to the new buffer with
+    /// their next call to [slice](Reader::slice). Until then, the old buffer
+    /// is kept alive.
+    ///
+    /// Fails with [Resize](CircularError::Resize), if the circular buffer
+    /// was created [with_buffer](Circular::with_buffer).
+    pub fn resize(&mut self, min_items: usize) -> Result<(), CircularError> {
+        self.idle();
+        self.update_readers();
+
+        let (allocate, options) = self.allocate.as_ref().ok_or(CircularError::Resize)?;
+
+        // readers only move forward, so this covers all items they did not read
+        let oldest = self
+            .readers
+            .iter()
+            .map(|r| r.position.load(Ordering::Acquire))
+            .min()
+            .unwrap_or(self.position);
+        let unread = (self.position - oldest) as usize;
+
+        let min_items = std::cmp::max(min_items, unread);
+        let buffer = allocate(min_items, options.clone())
+            .map_err(|e| CircularError::allocation::<T>(e, min_items, options))?;
+        if buffer.is_read_only() {
+            return Err(CircularError::ReadOnly);
+        }
 
-        if state.writer_offset + n >= self.buffer.capacity() {
-            state.writer_ab = !state.writer_ab;
+        // positions stay valid, items move to their offset in the new buffer
+        unsafe {
+            let old = (oldest % self.buffer.capacity() as u64) as usize;
+            let new = (oldest % buffer.capacity() as u64) as usize;
+            std::ptr::copy_nonoverlapping(
+                self.buffer.slice_with_offset(old).as_ptr(),
+                buffer.slice_with_offset_mut(new).as_mut_ptr(),
+                unread,
+            );
+            buffer.mirror(new, unread);
         }
-        state.writer_offset = (state.writer_offset + n) % self.buffer.capacity();
+
+        let buffer = Arc::new(buffer);
+        let mut current = self.state.buffer.lock().unwrap();
+        *current = buffer.clone();
+        self.state.buffer_generation.fetch_add(1, Ordering::Release);
+        drop(current);
+
+        self.buffer = buffer;
+        self.last_space = 0;
+        self.blocked = 0;
+        Ok(())
+    }
+
+    /// Grow the buffer automatically according to `policy`, when the writer
+    /// keeps finding it full. `None` disables growing.
+    pub fn grow_when_full(&mut self, policy: Option<GrowPolicy>) {
+        self.grow = policy;
+        self.blocked = 0;
+    }
+
+    /// Account for a full buffer and grow it, if requested by the policy.
+    fn grow(&mut self) -> bool {
+        let Some(policy) = self.grow else {
+            return false;
+        };
+        let capacity = self.buffer.capacity();
+        if capacity >= policy.max_items {
+            return false;
+        }
+
+        self.blocked += 1;
+        if self.blocked < policy.blocked {
+            return false;
+        }
+
+        let items = std::cmp::min(2 * capacity, policy.max_items);
+        self.resize(items).is_ok()
     }
 }
 
@@ -241,9 +897,9 @@ where
     M: Metadata,
 {
     fn drop(&mut self) {
-        let mut state = self.state.lock().unwrap();
-        state.writer_done = true;
-        for (_, r) in state.readers.iter_mut() {
+        self.idle();
+        self.state.writer_done.store(true, Ordering::SeqCst);
+        for (_, r) in self.state.readers.lock().unwrap().iter() {
             r.reader_notifier.notify();
         }
     }
@@ -256,9 +912,13 @@ where
     M: Metadata,
 {
     id: usize,
+    /// Position of the reader, which is only updated by the reader.
+    position: u64,
     last_space: usize,
+    reader: Arc<ReaderState<N, M>>,
     buffer: Arc<DoubleMappedBuffer<T>>,
-    state: Arc<Mutex<State<N, M>>>,
+    buffer_generation: usize,
+    state: Arc<State<T, N, M>>,
 }
 
 impl<T, N, M> Reader<T, N, M>
@@ -266,40 +926,46 @@ where
     N: Notifier,
     M: Metadata,
 {
-    fn space_and_offset_and_meta(&self, arm: bool) -> (usize, usize, bool, Vec<M::Item>) {
-        let mut state = self.state.lock().unwrap();
+    /// Number of available items and whether the writer is done.
+    fn space(&mut self) -> (usize, bool) {
+        let done = self.state.writer_done.load(Ordering::SeqCst);
+        let writer = self.state.writer.load(Ordering::SeqCst);
 
-        let capacity = self.buffer.capacity();
-        let done = state.writer_done;
-        let w_off = state.writer_offset;
-        let w_ab = state.writer_ab;
-
-        let my = unsafe { state.readers.get_unchecked_mut(self.id) };
-        let r_off = my.offset;
-        let r_ab = my.ab;
-
-        let space = if r_off > w_off {
-            w_off + capacity - r_off
-        } else if r_off < w_off {
-            w_off - r_off
-        } else if r_ab == w_ab {
-            0
-        } else {
-            capacity
-        };
+        // switch to the new buffer, if the writer resized it
+        if self.state.buffer_generation.load(Ordering::Acquire) != self.buffer_generation {
+            let buffer = self.state.buffer.lock().unwrap();
+            self.buffer = buffer.clone();
+            self.buffer_generation = self.state.buffer_generation.load(Ordering::Relaxed);
+        }
+
+        ((writer - self.position) as usize, done)
+    }
 
+    fn space_and_offset(&mut self, arm: bool) -> (usize, usize, bool) {
+        let (mut space, mut done) = self.space();
         if space == 0 && arm {
-            my.reader_notifier.arm();
+            self.reader.reader_notifier.arm();
+            (space, done) = self.space();
         }
 
-        (space, r_off, done, my.meta.get())
+        let offset = (self.position % self.buffer.capacity() as u64) as usize;
+        (space, offset, done)
     }
 
     /// Get a slice with the items available to read.
     ///
     /// Returns `None` if the reader was dropped and all data was read.
     pub fn slice(&mut self, arm: bool) -> Option<(&[T], Vec<M::Item>)> {
-        let (space, offset, done, tags) = self.space_and_offset_and_meta(arm);
+        let (space, offset, done, tags) = if M::VOID {
+            let (space, offset, done) = self.space_and_offset(arm);
+            (space, offset, done, Vec::new())
+        } else {
+            let reader = self.reader.clone();
+            let meta = reader.meta.lock().unwrap();
+            let (space, offset, done) = self.space_and_offset(arm);
+            (space, offset, done, meta.get())
+        };
+
         self.last_space = space;
         if space == 0 && done {
             None
@@ -318,22 +984,21 @@ where
             return;
         }
 
-        debug_assert!(self.space_and_offset_and_meta(false).0 >= n);
+        debug_assert!((self.state.writer.load(Ordering::SeqCst) - self.position) as usize >= n);
 
         assert!(n <= self.last_space, "vmcircbuffer: consumed too much!");
         self.last_space -= n;
+        self.position += n as u64;
 
-        let mut state = self.state.lock().unwrap();
-        let my = unsafe { state.readers.get_unchecked_mut(self.id) };
-
-        my.meta.consume(n);
-
-        if my.offset + n >= self.buffer.capacity() {
-            my.ab = !my.ab;
+        if M::VOID {
+            self.reader.position.store(self.position, Ordering::SeqCst);
+        } else {
+            let mut meta = self.reader.meta.lock().unwrap();
+            meta.consume(n);
+            self.reader.position.store(self.position, Ordering::SeqCst);
         }
-        my.offset = (my.offset + n) % self.buffer.capacity();
 
-        my.writer_notifier.notify();
+        self.reader.writer_notifier.notify();
     }
 }
 
@@ -343,8 +1008,24 @@ where
     M: Metadata,
 {
     fn drop(&mut self) {
-        let mut state = self.state.lock().unwrap();
-        let mut s = state.readers.remove(self.id);
-        s.writer_notifier.notify();
+        if let Some(owned) = self.state.owned.get() {
+            if Arc::ptr_eq(owned, &self.reader) {
+                // items that were not taken are dropped with the reader, before
+                // the writer sees that it is gone and drops further items
+                let writer = self.state.writer.load(Ordering::Acquire);
+                let buffer = self.state.buffer.lock().unwrap();
+                let n = (writer - self.position) as usize;
+                unsafe { drop_items(&buffer, self.position, n) };
+                drop(buffer);
+                self.position = writer;
+                owned.position.store(writer, Ordering::Release);
+            }
+        }
+
+        let mut readers = self.state.readers.lock().unwrap();
+        readers.remove(self.id);
+        self.state.readers_generation.fetch_add(1, Ordering::SeqCst);
+        drop(readers);
+        self.reader.writer_notifier.notify();
     }
 }
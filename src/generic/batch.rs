@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::slice;
+use std::sync::Mutex;
+
+use crate::double_mapped_buffer::Pod;
+
+use super::Metadata;
+use super::Notifier;
+use super::Writer;
+
+impl<T, N, M> Writer<T, N, M>
+where
+    T: Pod,
+    N: Notifier,
+    M: Metadata,
+{
+    /// Get the output buffer space as a [WriteBatch] that can be split into
+    /// [chunks](Chunk), which are filled in parallel. Might be empty.
+    ///
+    /// Committed chunks are produced automatically, once all chunks before
+    /// them are committed.
+    pub fn batch(&mut self, arm: bool) -> WriteBatch<'_, T, N, M> {
+        let s = self.slice(arm);
+        let (items, len) = (s.as_mut_ptr(), s.len());
+        WriteBatch {
+            progress: Mutex::new(Progress {
+                writer: self,
+                split: 0,
+                pending: VecDeque::new(),
+                first: 0,
+                produced: 0,
+            }),
+            items,
+            len,
+        }
+    }
+}
+
+/// Output space of a [Writer] that is split into [chunks](Chunk), which can
+/// be filled in parallel, e.g., in a thread pool.
+///
+/// Chunks are [committed](Chunk::commit) in any order. Items are produced in
+/// order, i.e., a chunk is produced, once it and all chunks before it are
+/// committed. Chunks that are dropped without being committed, and all chunks
+/// after them, are not produced.
+///
+/// ```
+/// # use vmcircbuffer::generic::{Circular, NoMetadata, Notifier};
+/// # struct MyNotifier;
+/// # impl Notifier for MyNotifier {
+/// #     fn arm(&mut self) {}
+/// #     fn notify(&mut self) {}
+/// # }
+/// let mut w = Circular::with_capacity::<u32, MyNotifier, NoMetadata>(1024).unwrap();
+/// let mut r = w.add_reader(MyNotifier, MyNotifier);
+///
+/// let batch = w.batch(false);
+/// std::thread::scope(|s| {
+///     for (i, mut chunk) in batch.chunks(256).into_iter().take(4).enumerate() {
+///         s.spawn(move || {
+///             chunk.fill(i as u32);
+///             chunk.commit(Vec::new());
+///         });
+///     }
+/// });
+/// assert_eq!(batch.produced(), 1024);
+/// drop(batch);
+///
+/// assert_eq!(r.slice(false).unwrap().0[768], 3);
+/// ```
+pub struct WriteBatch<'a, T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    progress: Mutex<Progress<'a, T, N, M>>,
+    items: *mut T,
+    len: usize,
+}
+
+struct Progress<'a, T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    writer: &'a mut Writer<T, N, M>,
+    /// Number of items that are split into chunks.
+    split: usize,
+    /// Chunks that are not produced yet, in order.
+    pending: VecDeque<Pending<M>>,
+    /// Index of the first pending chunk.
+    first: usize,
+    /// Number of produced items.
+    produced: usize,
+}
+
+struct Pending<M: Metadata> {
+    len: usize,
+    /// Metadata of the chunk, once it is committed.
+    meta: Option<Vec<M::Item>>,
+}
+
+unsafe impl<T, N, M> Sync for WriteBatch<'_, T, N, M>
+where
+    T: Send,
+    N: Notifier,
+    M: Metadata,
+    M::Item: Send,
+    Writer<T, N, M>: Send,
+{
+}
+
+impl<'a, T, N, M> WriteBatch<'a, T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    /// Number of items of the batch.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the batch has no items.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of items that are not split into chunks yet.
+    pub fn remaining(&self) -> usize {
+        self.len - self.progress.lock().unwrap().split
+    }
+
+    /// Number of items that were produced.
+    pub fn produced(&self) -> usize {
+        self.progress.lock().unwrap().produced
+    }
+
+    /// Split the next `n` items off the batch.
+    ///
+    /// # Panics
+    ///
+    /// If more than the [remaining](WriteBatch::remaining) items are split.
+    pub fn split(&self, n: usize) -> Chunk<'_, 'a, T, N, M> {
+        let mut progress = self.progress.lock().unwrap();
+        self.split_locked(&mut progress, n)
+    }
+
+    /// Split the remaining items into chunks of `chunk_size` items. The last
+    /// chunk might be shorter.
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_size` is zero.
+    pub fn chunks(&self, chunk_size: usize) -> Vec<Chunk<'_, 'a, T, N, M>> {
+        assert!(chunk_size > 0, "vmcircbuffer: chunk size is zero");
+        let mut progress = self.progress.lock().unwrap();
+        let mut chunks = Vec::new();
+        while progress.split < self.len {
+            let n = chunk_size.min(self.len - progress.split);
+            chunks.push(self.split_locked(&mut progress, n));
+        }
+        chunks
+    }
+
+    fn split_locked(
+        &self,
+        progress: &mut Progress<'a, T, N, M>,
+        n: usize,
+    ) -> Chunk<'_, 'a, T, N, M> {
+        let start = progress.split;
+        assert!(n <= self.len - start, "vmcircbuffer: split too much");
+
+        progress.split += n;
+        let index = progress.first + progress.pending.len();
+        progress.pending.push_back(Pending { len: n, meta: None });
+
+        Chunk {
+            batch: self,
+            index,
+            items: unsafe { self.items.add(start) },
+            len: n,
+            _p: PhantomData,
+        }
+    }
+
+    fn commit(&self, index: usize, meta: Vec<M::Item>) {
+        let mut progress = self.progress.lock().unwrap();
+        let i = index - progress.first;
+        progress.pending[i].meta = Some(meta);
+
+        while let Some(Pending { meta: Some(_), .. }) = progress.pending.front() {
+            let Pending { len, meta } = progress.pending.pop_front().unwrap();
+            progress.first += 1;
+            progress.produced += len;
+            // the writer stays busy, while other chunks are filled
+            unsafe { progress.writer.publish(len, meta.unwrap()) };
+        }
+    }
+}
+
+impl<T, N, M> Drop for WriteBatch<'_, T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    fn drop(&mut self) {
+        self.progress.get_mut().unwrap().writer.idle();
+    }
+}
+
+/// Disjoint part of a [WriteBatch] that can be filled on another thread.
+pub struct Chunk<'b, 'a, T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    batch: &'b WriteBatch<'a, T, N, M>,
+    index: usize,
+    items: *mut T,
+    len: usize,
+    _p: PhantomData<&'b mut [T]>,
+}
+
+unsafe impl<'a, T, N, M> Send for Chunk<'_, 'a, T, N, M>
+where
+    T: Send,
+    N: Notifier,
+    M: Metadata,
+    WriteBatch<'a, T, N, M>: Sync,
+{
+}
+
+impl<T, N, M> Chunk<'_, '_, T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    /// Mark the chunk as filled, attaching metadata, whose offsets are
+    /// relative to the start of the chunk.
+    ///
+    /// The chunk is produced, once all chunks before it are committed.
+    pub fn commit(self, meta: Vec<M::Item>) {
+        self.batch.commit(self.index, meta);
+    }
+}
+
+impl<T, N, M> Deref for Chunk<'_, '_, T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.items, self.len) }
+    }
+}
+
+impl<T, N, M> DerefMut for Chunk<'_, '_, T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.items, self.len) }
+    }
+}
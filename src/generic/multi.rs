@@ -0,0 +1,310 @@
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::slice;
+use std::sync::{Arc, Mutex};
+
+use crate::double_mapped_buffer::Pod;
+
+use super::Metadata;
+use super::Notifier;
+use super::Notify;
+use super::Reader;
+use super::Writer;
+
+impl<T, N, M> Writer<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    /// Turn the writer into a [MultiWriter] that can be cloned to append
+    /// from several producers.
+    ///
+    /// `notifier` is armed, when a producer does not find enough space for
+    /// its reservation, and notified, when reservations are committed or
+    /// released. Readers notify their writer notifiers, when they consume
+    /// items, like for a single writer.
+    ///
+    /// The buffer is not [resized](Writer::resize) by a [MultiWriter] and
+    /// automatic [reclaim](Writer::reclaim_after) is disabled, since
+    /// producers can hold reservations at any time.
+    pub fn into_multi(mut self, notifier: N) -> MultiWriter<T, N, M> {
+        self.idle();
+        self.reclaim = false;
+        self.state.reclaim.lock().unwrap().after = None;
+
+        MultiWriter {
+            shared: Arc::new(Shared {
+                inner: Mutex::new(Inner {
+                    writer: self,
+                    reserved: 0,
+                    pending: VecDeque::new(),
+                    first: 0,
+                }),
+                producers: Notify::new(notifier),
+            }),
+        }
+    }
+}
+
+/// Writer for a generic circular buffer that can be cloned to append items
+/// from several producers.
+///
+/// Producers [reserve](MultiWriter::reserve) a contiguous range of items,
+/// fill it, and [commit](Reservation::commit) it. Reservations are handed to
+/// the readers in the order in which they were reserved, i.e., once all
+/// earlier reservations are committed.
+///
+/// ```
+/// # use vmcircbuffer::generic::{Circular, NoMetadata, Notifier};
+/// # struct MyNotifier;
+/// # impl Notifier for MyNotifier {
+/// #     fn arm(&mut self) {}
+/// #     fn notify(&mut self) {}
+/// # }
+/// let w = Circular::with_capacity::<u32, MyNotifier, NoMetadata>(1024).unwrap();
+/// let w = w.into_multi(MyNotifier);
+/// let mut r = w.add_reader(MyNotifier, MyNotifier);
+///
+/// let mut first = w.reserve(2, false).unwrap();
+/// let mut second = w.clone().reserve(1, false).unwrap();
+/// second[0] = 3;
+/// second.commit(Vec::new());
+/// assert!(r.slice(false).unwrap().0.is_empty());
+///
+/// first.copy_from_slice(&[1, 2]);
+/// first.commit(Vec::new());
+/// assert_eq!(r.slice(false).unwrap().0, &[1, 2, 3]);
+/// ```
+pub struct MultiWriter<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    shared: Arc<Shared<T, N, M>>,
+}
+
+struct Shared<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    inner: Mutex<Inner<T, N, M>>,
+    producers: Notify<N>,
+}
+
+struct Inner<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    writer: Writer<T, N, M>,
+    /// Number of items that are reserved after the position of the writer.
+    reserved: usize,
+    /// Reservations that are not produced yet, in reservation order.
+    pending: VecDeque<Pending<M>>,
+    /// Id of the first pending reservation.
+    first: u64,
+}
+
+struct Pending<M: Metadata> {
+    len: usize,
+    /// Metadata of the reservation, once it is committed.
+    meta: Option<Vec<M::Item>>,
+}
+
+impl<T, N, M> Inner<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    /// Produce the committed reservations at the front.
+    fn produce(&mut self) {
+        while let Some(Pending { meta: Some(_), .. }) = self.pending.front() {
+            let Pending { len, meta } = self.pending.pop_front().unwrap();
+            self.first += 1;
+            self.reserved -= len;
+            // the reservation was part of the free space
+            self.writer.last_space = len;
+            unsafe { self.writer.assume_init(len, meta.unwrap()) };
+        }
+    }
+}
+
+impl<T, N, M> Clone for MultiWriter<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    fn clone(&self) -> Self {
+        MultiWriter {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T, N, M> MultiWriter<T, N, M>
+where
+    T: Pod,
+    N: Notifier,
+    M: Metadata,
+{
+    /// Add a [Reader] to the buffer.
+    pub fn add_reader(&self, reader_notifier: N, writer_notifier: N) -> Reader<T, N, M> {
+        let inner = self.shared.inner.lock().unwrap();
+        inner.writer.add_reader(reader_notifier, writer_notifier)
+    }
+
+    /// The capacity of the buffer, i.e., the largest possible reservation.
+    pub fn capacity(&self) -> usize {
+        self.shared.inner.lock().unwrap().writer.buffer.capacity()
+    }
+
+    /// Reserve `n` contiguous items after all earlier reservations.
+    ///
+    /// Returns `None`, if there is not enough space. If `arm` is set, the
+    /// notifiers of the producers and of the slowest reader are armed in this
+    /// case.
+    ///
+    /// **Reservations are handed to the readers in order.** If a reservation
+    /// is dropped without being committed, while later reservations exist, it
+    /// cannot be skipped. Its items are zeroed and handed to the readers like
+    /// committed items. Only the last reservation is released, when it is
+    /// dropped.
+    ///
+    /// # Panics
+    ///
+    /// If more than the [capacity](MultiWriter::capacity) is reserved.
+    pub fn reserve(&self, n: usize, arm: bool) -> Option<Reservation<T, N, M>> {
+        let mut inner = self.shared.inner.lock().unwrap();
+        let capacity = inner.writer.buffer.capacity();
+        assert!(
+            n <= capacity,
+            "vmcircbuffer: reserved more than the capacity"
+        );
+
+        let needed = inner.reserved + n;
+        let (space, offset) = inner.writer.space_and_offset_for(needed, arm);
+        if space < needed {
+            if arm {
+                self.shared.producers.arm();
+            }
+            return None;
+        }
+
+        // start at the offset of the reservation, which is mirrored on produce
+        let offset = (offset + inner.reserved) % capacity;
+        let items = unsafe {
+            inner.writer.buffer.slice_with_offset_uninit(offset)[0..n]
+                .as_mut_ptr()
+                .cast::<T>()
+        };
+        let id = inner.first + inner.pending.len() as u64;
+        inner.pending.push_back(Pending { len: n, meta: None });
+        inner.reserved = needed;
+
+        Some(Reservation {
+            shared: self.shared.clone(),
+            id,
+            items,
+            len: n,
+            committed: false,
+        })
+    }
+}
+
+/// Contiguous range of items that is reserved by a [MultiWriter].
+///
+/// The items are handed to the readers, once the reservation and all earlier
+/// reservations are [committed](Reservation::commit). A reservation that is
+/// dropped without being committed is released, if it is the last one, and
+/// otherwise committed with all items zeroed (see [MultiWriter::reserve]).
+pub struct Reservation<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    shared: Arc<Shared<T, N, M>>,
+    id: u64,
+    items: *mut T,
+    len: usize,
+    committed: bool,
+}
+
+unsafe impl<T, N, M> Send for Reservation<T, N, M>
+where
+    T: Send + Sync,
+    N: Notifier + Send,
+    M: Metadata + Send,
+{
+}
+
+impl<T, N, M> Reservation<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    /// Commit the reservation with metadata, whose offsets are relative to
+    /// the start of the reservation.
+    pub fn commit(mut self, meta: Vec<M::Item>) {
+        self.committed = true;
+        self.finish(Some(meta));
+    }
+
+    fn finish(&mut self, meta: Option<Vec<M::Item>>) {
+        let mut inner = self.shared.inner.lock().unwrap();
+        let i = (self.id - inner.first) as usize;
+
+        match meta {
+            Some(meta) => inner.pending[i].meta = Some(meta),
+            None if i + 1 == inner.pending.len() => {
+                inner.pending.pop_back();
+                inner.reserved -= self.len;
+            }
+            None => {
+                // readers must not see stale items, and every bit pattern is
+                // valid, since reservations are only made for Pod types
+                unsafe { ptr::write_bytes(self.items, 0, self.len) };
+                inner.pending[i].meta = Some(Vec::new());
+            }
+        }
+
+        inner.produce();
+        drop(inner);
+        self.shared.producers.notify();
+    }
+}
+
+impl<T, N, M> Deref for Reservation<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.items, self.len) }
+    }
+}
+
+impl<T, N, M> DerefMut for Reservation<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.items, self.len) }
+    }
+}
+
+impl<T, N, M> Drop for Reservation<T, N, M>
+where
+    N: Notifier,
+    M: Metadata,
+{
+    fn drop(&mut self) {
+        if !self.committed {
+            self.finish(None);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::iter::FusedIterator;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::Arc;
+
+use super::CircularError;
+use super::DoubleMappedBuffer;
+use super::GrowPolicy;
+use super::NoMetadata;
+use super::Notifier;
+use super::Reader;
+use super::Writer;
+
+impl<T, N> Writer<T, N, NoMetadata>
+where
+    N: Notifier,
+{
+    /// Turn the buffer into a bounded queue for owned items.
+    ///
+    /// The [OwnedWriter] moves items into the buffer and the single
+    /// [OwnedReader] moves them out again, i.e., types that are not [Copy],
+    /// like `String` or `Vec<u8>`, are handed over without allocations. Items
+    /// that are not taken, when the reader is dropped, are dropped with it.
+    /// Items that are pushed afterwards are dropped right away.
+    ///
+    /// Fails with [Readers](CircularError::Readers), if the buffer already
+    /// has readers.
+    ///
+    /// ```
+    /// # use vmcircbuffer::generic::{Circular, NoMetadata, Notifier};
+    /// # struct MyNotifier;
+    /// # impl Notifier for MyNotifier {
+    /// #     fn arm(&mut self) {}
+    /// #     fn notify(&mut self) {}
+    /// # }
+    /// let w = Circular::with_capacity::<String, MyNotifier, NoMetadata>(16).unwrap();
+    /// let (mut w, mut r) = w.into_owned(MyNotifier, MyNotifier).unwrap();
+    ///
+    /// w.push("hello".to_string(), false).unwrap();
+    /// w.push("world".to_string(), false).unwrap();
+    ///
+    /// let items: Vec<String> = r.take(false).unwrap().collect();
+    /// assert_eq!(items, ["hello", "world"]);
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn into_owned(
+        self,
+        reader_notifier: N,
+        writer_notifier: N,
+    ) -> Result<(OwnedWriter<T, N>, OwnedReader<T, N>), CircularError> {
+        if !self.state.readers.lock().unwrap().is_empty() {
+            return Err(CircularError::Readers);
+        }
+
+        let reader = self.add_reader(reader_notifier, writer_notifier);
+        let _ = self.state.owned.set(reader.reader.clone());
+        Ok((OwnedWriter { writer: self }, OwnedReader { reader }))
+    }
+}
+
+/// Writer that moves items into a buffer, created through
+/// [into_owned](Writer::into_owned).
+pub struct OwnedWriter<T, N>
+where
+    N: Notifier,
+{
+    writer: Writer<T, N, NoMetadata>,
+}
+
+impl<T, N> OwnedWriter<T, N>
+where
+    N: Notifier,
+{
+    /// Move `item` into the buffer.
+    ///
+    /// If the buffer is full, the item is returned.
+    pub fn push(&mut self, item: T, arm: bool) -> Result<(), T> {
+        match self.slice(arm) {
+            [] => Err(item),
+            s => {
+                s[0].write(item);
+                unsafe { self.writer.assume_init(1, Vec::new()) };
+                Ok(())
+            }
+        }
+    }
+
+    /// Get a slice of free slots to write items to. Might be empty.
+    ///
+    /// Written items are handed to the reader with
+    /// [assume_init](OwnedWriter::assume_init). Items that are written but
+    /// not handed to the reader are leaked.
+    pub fn slice(&mut self, arm: bool) -> &mut [MaybeUninit<T>] {
+        // the slots hold no items, since the reader moved them out
+        unsafe { self.writer.slice_uninit(arm) }
+    }
+
+    /// Indicates that the first `n` items of the last provided slice were
+    /// initialized and hands them to the reader.
+    ///
+    /// # Safety
+    ///
+    /// The first `n` items of the last provided slice have to be initialized.
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    pub unsafe fn assume_init(&mut self, n: usize) {
+        self.writer.assume_init(n, Vec::new());
+    }
+
+    /// Resize the buffer, such that it can hold at least `min_items` items.
+    ///
+    /// See [Writer::resize].
+    pub fn resize(&mut self, min_items: usize) -> Result<(), CircularError> {
+        self.writer.resize(min_items)
+    }
+
+    /// Grow the buffer automatically according to `policy`, when the writer
+    /// keeps finding it full. `None` disables growing.
+    pub fn grow_when_full(&mut self, policy: Option<GrowPolicy>) {
+        self.writer.grow_when_full(policy)
+    }
+}
+
+/// Reader that moves items out of a buffer, created through
+/// [into_owned](Writer::into_owned).
+pub struct OwnedReader<T, N>
+where
+    N: Notifier,
+{
+    reader: Reader<T, N, NoMetadata>,
+}
+
+impl<T, N> OwnedReader<T, N>
+where
+    N: Notifier,
+{
+    /// Get a slice with the items available to read, without taking them.
+    ///
+    /// Returns `None` if the writer was dropped and all items were taken.
+    pub fn slice(&mut self, arm: bool) -> Option<&[T]> {
+        self.reader.slice(arm).map(|(s, _)| s)
+    }
+
+    /// Take the items that are available to read.
+    ///
+    /// The returned iterator moves the items out of the buffer. Items that
+    /// are not iterated stay in the buffer. Returns `None` if the writer was
+    /// dropped and all items were taken.
+    pub fn take(&mut self, arm: bool) -> Option<Drain<'_, T, N>> {
+        let (s, _) = self.reader.slice(arm)?;
+        let (items, len) = (s.as_ptr(), s.len());
+        Some(Drain {
+            _buffer: self.reader.buffer.clone(),
+            reader: &mut self.reader,
+            items,
+            len,
+            taken: 0,
+        })
+    }
+}
+
+/// Iterator that moves items out of an [OwnedReader].
+///
+/// Every item is consumed, as it is taken. Items that are not taken stay in
+/// the buffer, also if the iterator is leaked.
+pub struct Drain<'a, T, N>
+where
+    N: Notifier,
+{
+    reader: &'a mut Reader<T, N, NoMetadata>,
+    // keeps the items alive, if the writer resizes the buffer meanwhile
+    _buffer: Arc<DoubleMappedBuffer<T>>,
+    items: *const T,
+    len: usize,
+    taken: usize,
+}
+
+impl<T, N> Iterator for Drain<'_, T, N>
+where
+    N: Notifier,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.taken == self.len {
+            return None;
+        }
+        let item = unsafe { ptr::read(self.items.add(self.taken)) };
+        // the item is moved out, so the reader must not see it again
+        self.reader.consume(1);
+        self.taken += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len - self.taken;
+        (n, Some(n))
+    }
+}
+
+impl<T, N> ExactSizeIterator for Drain<'_, T, N> where N: Notifier {}
+
+impl<T, N> FusedIterator for Drain<'_, T, N> where N: Notifier {}
@@ -2,12 +2,21 @@
 //!
 //! - Thread-safe.
 //! - Supports multiple readers.
-//! - Generic over the item type.
+//! - Generic over the item type; safe writer slices for [Pod](double_mapped_buffer::Pod) items, uninitialized slices for all others.
 //! - Provides access to all items (not n-1).
 //! - Supports Linux, macOS, Windows, and Android.
 //! - [Sync](sync), [async](asynchronous), and [non-blocking](nonblocking) implementations.
 //! - [Generic](crate::generic) variant that allows specifying custom [Notifiers](crate::generic::Notifier) to ease integration.
+//! - [Owned](crate::generic::Writer::into_owned) mode that moves items, which are not `Copy`, through the buffer.
+//! - [Multi-producer](crate::generic::MultiWriter) writers that reserve and commit ranges, which are handed to readers in reservation order.
+//! - [Batches](crate::generic::WriteBatch) that split the output space into chunks, which are filled in parallel and produced in order.
+//! - [Single-producer/single-consumer](spsc) buffer with a lock-free fast path, in sync, async, and non-blocking flavours.
+//! - [Shared](shared) implementation for readers in other processes (Linux only).
+//! - [Persistent](persistent) implementation that is stored in a file and survives restarts (Linux only).
 //! - Underlying data structure (i.e., [DoubleMappedBuffer](double_mapped_buffer::DoubleMappedBuffer)) is exported to allow custom implementations.
+//! - Pluggable [Backends](double_mapped_buffer::Backend) that provide the memory of the buffer.
+//! - [Arena](double_mapped_buffer::Arena) that packs many small buffers into one mapping (Unix only).
+//! - Process-wide [accounting](double_mapped_buffer::accounting) of buffer memory with an optional budget.
 //!
 //! # Quick Start
 //!
@@ -59,24 +68,41 @@
 //! allows the circular buffer to present the available data sequentially,
 //! (i.e., as a slice) without having to worry about wrapping.
 //!
-//! On Unix-based systems, the mapping is setup with a temporary file. This file
-//! is created in the folder, determined through [std::env::temp_dir], which
-//! considers environment variables. This can be used, if the standard paths are
-//! not present of not writable on the platform.
+//! On Linux and Android, the mapping is setup with an anonymous memory file
+//! (`memfd_create`), which never touches the file system. On other Unix-based
+//! systems, it is setup with a temporary file. This file is created in the
+//! folder, determined through [std::env::temp_dir], which considers environment
+//! variables. This can be used, if the standard paths are not present of not
+//! writable on the platform. The
+//! [Backing](double_mapped_buffer::Backing) can also be selected explicitly
+//! through the [Options](double_mapped_buffer::Options) of the buffer.
 //!
 //! # Features
 //!
-//! The `async`, `nonblocking`, and `sync` feature flags, allow to disable the
+//! The `async`, `nonblocking`, `persistent`, `shared`, and `sync` feature flags, allow to disable the
 //! corresponding implementations. By default, all are enabled. In addition, the
 //! `generic` flag allows to disable the generic implementation, leaving only
 //! the [DoubleMappedBuffer](double_mapped_buffer::DoubleMappedBuffer).
+//!
+//! The [HeapBackend](double_mapped_buffer::HeapBackend) emulates the double
+//! mapping with a plain heap allocation. It is selected per buffer through the
+//! `with_backend` constructors, and automatically under Miri and on targets
+//! without `mmap`.
 
 #[cfg(feature = "async")]
 pub mod asynchronous;
 pub mod double_mapped_buffer;
+#[cfg(all(any(feature = "persistent", feature = "shared"), target_os = "linux"))]
+mod file_lock;
 #[cfg(feature = "generic")]
 pub mod generic;
 #[cfg(feature = "nonblocking")]
 pub mod nonblocking;
+#[cfg(all(feature = "persistent", target_os = "linux"))]
+pub mod persistent;
+#[cfg(all(feature = "shared", target_os = "linux"))]
+pub mod shared;
+#[cfg(feature = "spsc")]
+pub mod spsc;
 #[cfg(feature = "sync")]
 pub mod sync;
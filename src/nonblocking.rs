@@ -1,11 +1,19 @@
 //! Non-blocking Circular Buffer that can only check if data is available right now.
 
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+
+use crate::double_mapped_buffer::Backend;
+use crate::double_mapped_buffer::DoubleMappedBuffer;
+use crate::double_mapped_buffer::Options;
+use crate::double_mapped_buffer::Pod;
 use crate::generic;
 use crate::generic::CircularError;
+use crate::generic::GrowPolicy;
 use crate::generic::NoMetadata;
 use crate::generic::Notifier;
 
-struct NullNotifier;
+pub(crate) struct NullNotifier;
 
 impl Notifier for NullNotifier {
     fn arm(&mut self) {}
@@ -28,9 +36,34 @@ impl Circular {
     ///
     /// The size is the least common multiple of the page size and the size of `T`.
     pub fn with_capacity<T>(min_items: usize) -> Result<Writer<T>, CircularError> {
-        let writer = generic::Circular::with_capacity(min_items)?;
+        Self::with_options(min_items, Options::default())
+    }
 
-        Ok(Writer { writer })
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with the underlying buffer configured through [Options].
+    pub fn with_options<T>(min_items: usize, options: Options) -> Result<Writer<T>, CircularError> {
+        let writer = generic::Circular::with_options(min_items, options)?;
+        Ok(Self::from_writer(writer))
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with memory that is provided by [Backend] `B`.
+    pub fn with_backend<T, B: Backend>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<Writer<T>, CircularError> {
+        let writer = generic::Circular::with_backend::<T, B, _, _>(min_items, options)?;
+        Ok(Self::from_writer(writer))
+    }
+
+    /// Create a circular buffer on top of an existing [DoubleMappedBuffer].
+    pub fn with_buffer<T>(buffer: DoubleMappedBuffer<T>) -> Result<Writer<T>, CircularError> {
+        let writer = generic::Circular::with_buffer(buffer)?;
+        Ok(Self::from_writer(writer))
+    }
+
+    fn from_writer<T>(writer: generic::Writer<T, NullNotifier, NoMetadata>) -> Writer<T> {
+        Writer { writer }
     }
 }
 
@@ -54,10 +87,41 @@ impl<T> Writer<T> {
     ///
     /// This function return immediately. The slice might be [empty](slice::is_empty).
     #[inline]
-    pub fn try_slice(&mut self) -> &mut [T] {
+    pub fn try_slice(&mut self) -> &mut [T]
+    where
+        T: Pod,
+    {
         self.writer.slice(false)
     }
 
+    /// Get a slice to the free slots as possibly uninitialized items.
+    ///
+    /// This function return immediately. The slice might be [empty](slice::is_empty).
+    /// Written items are handed to the readers with
+    /// [assume_init](Writer::assume_init).
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::slice_uninit].
+    #[inline]
+    pub unsafe fn try_slice_uninit(&mut self) -> &mut [MaybeUninit<T>] {
+        self.writer.slice_uninit(false)
+    }
+
+    /// Resize the buffer, such that it can hold at least `min_items` items,
+    /// preserving unread items and readers.
+    ///
+    /// See [generic::Writer::resize].
+    pub fn resize(&mut self, min_items: usize) -> Result<(), CircularError> {
+        self.writer.resize(min_items)
+    }
+
+    /// Grow the buffer automatically, when the writer keeps finding the buffer full.
+    /// `None` disables growing.
+    pub fn grow_when_full(&mut self, policy: Option<GrowPolicy>) {
+        self.writer.grow_when_full(policy)
+    }
+
     /// Indicates that `n` items were written to the output buffer.
     ///
     /// It is ok if `n` is zero.
@@ -66,9 +130,113 @@ impl<T> Writer<T> {
     ///
     /// If produced more than space was available in the last provided slice.
     #[inline]
-    pub fn produce(&mut self, n: usize) {
+    pub fn produce(&mut self, n: usize)
+    where
+        T: Pod,
+    {
         self.writer.produce(n, Vec::new());
     }
+
+    /// Indicates that the first `n` items of the last provided slice were
+    /// initialized.
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::assume_init].
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    #[inline]
+    pub unsafe fn assume_init(&mut self, n: usize) {
+        self.writer.assume_init(n, Vec::new());
+    }
+
+    /// Turn the writer into a [MultiWriter] that can be cloned to append
+    /// from several producers.
+    ///
+    /// See [generic::Writer::into_multi].
+    pub fn into_multi(self) -> MultiWriter<T> {
+        MultiWriter {
+            writer: self.writer.into_multi(NullNotifier),
+        }
+    }
+}
+
+/// Writer for a non-blocking circular buffer with items of type `T` that can
+/// be cloned to append items from several producers.
+///
+/// Items are handed to the readers in the order of the reservations. See
+/// [generic::MultiWriter].
+pub struct MultiWriter<T> {
+    writer: generic::MultiWriter<T, NullNotifier, NoMetadata>,
+}
+
+impl<T> Clone for MultiWriter<T> {
+    fn clone(&self) -> Self {
+        MultiWriter {
+            writer: self.writer.clone(),
+        }
+    }
+}
+
+impl<T: Pod> MultiWriter<T> {
+    /// Add a reader to the buffer.
+    pub fn add_reader(&self) -> Reader<T> {
+        let reader = self.writer.add_reader(NullNotifier, NullNotifier);
+        Reader { reader }
+    }
+
+    /// The capacity of the buffer, i.e., the largest possible reservation.
+    pub fn capacity(&self) -> usize {
+        self.writer.capacity()
+    }
+
+    /// Reserve `n` items after all earlier reservations, if there is enough
+    /// space right now.
+    ///
+    /// Dropping a reservation without committing it, while later reservations
+    /// exist, hands zeroed items to the readers (see
+    /// [generic::MultiWriter::reserve]).
+    ///
+    /// # Panics
+    ///
+    /// If more than the [capacity](MultiWriter::capacity) is reserved.
+    #[inline]
+    pub fn try_reserve(&self, n: usize) -> Option<Reservation<T>> {
+        self.writer
+            .reserve(n, false)
+            .map(|reservation| Reservation { reservation })
+    }
+}
+
+/// Items that are reserved by a non-blocking [MultiWriter].
+///
+/// See [generic::Reservation].
+pub struct Reservation<T> {
+    reservation: generic::Reservation<T, NullNotifier, NoMetadata>,
+}
+
+impl<T> Reservation<T> {
+    /// Hand the items to the readers, once all earlier reservations are
+    /// committed.
+    pub fn commit(self) {
+        self.reservation.commit(Vec::new());
+    }
+}
+
+impl<T> Deref for Reservation<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.reservation
+    }
+}
+
+impl<T> DerefMut for Reservation<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.reservation
+    }
 }
 
 /// ReaderState for a non-blocking circular buffer with items of type `T`.
@@ -0,0 +1,413 @@
+//! Circular Buffer that is stored in a file and survives restarts.
+//!
+//! The [Writer](crate::persistent::Writer) stores the buffer in a
+//! [file](crate::double_mapped_buffer::DoubleMappedBuffer::open_file) that is
+//! not removed, when it is dropped. The offset of the writer and the offsets
+//! of up to [MAX_READERS](crate::persistent::MAX_READERS) readers are kept in
+//! the header page of the file. After a restart or crash, the writer reopens
+//! the file and continues where it stopped. [Readers](crate::persistent::Reader)
+//! are identified by an index and resume at the position, where they stopped
+//! consuming.
+//!
+//! Writer and readers hold a lock on a byte of the file, while they are
+//! attached. These are open file description locks, which the kernel releases,
+//! when the process terminates. A reader that crashed without detaching is,
+//! therefore, detected by the writer and does not hold it back, also after a
+//! reboot. A reader that opens a buffer, whose writer crashed, treats it like
+//! a detached writer.
+//!
+//! Only attached readers hold back the writer. Without readers, the writer
+//! overwrites the oldest items, i.e., the file holds the last items that were
+//! written, like a flight recorder. If a reader was lapped, while it was not
+//! attached, it resumes with the oldest item in the buffer. Note that these
+//! items might still be overwritten by a write that was in progress, while the
+//! reader attached.
+//!
+//! Changes are written to disk by the operating system at some point. Use
+//! [flush](crate::persistent::Writer::flush) to make them durable.
+//!
+//! The implementation is non-blocking, i.e., it can only check if data or
+//! space is available right now.
+//!
+//! Only available on Linux.
+
+use std::io;
+use std::mem;
+use std::os::unix::io::BorrowedFd;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use thiserror::Error;
+
+use crate::double_mapped_buffer::DoubleMappedBuffer;
+use crate::double_mapped_buffer::DoubleMappedBufferError;
+use crate::double_mapped_buffer::Options;
+use crate::double_mapped_buffer::Pod;
+use crate::file_lock::{locked, try_lock, unlock};
+
+/// Maximum number of readers of a persistent buffer.
+pub const MAX_READERS: usize = 32;
+
+/// Errors setting up a persistent buffer.
+#[derive(Error, Debug)]
+pub enum PersistentError {
+    /// Failed to create or open the underlying buffer.
+    #[error("Failed to set up double mapped buffer.")]
+    Buffer(#[from] DoubleMappedBufferError),
+    /// The header page cannot hold the state.
+    #[error("Header page too small for buffer state.")]
+    Header,
+    /// Another writer is attached to the buffer.
+    #[error("Buffer already has a writer.")]
+    WriterActive,
+    /// Another reader with the same index is attached to the buffer.
+    #[error("Reader already attached.")]
+    ReaderActive,
+    /// The reader index is out of range.
+    #[error("Invalid reader index.")]
+    InvalidReader,
+    /// Failed to lock a byte of the buffer file.
+    #[error("Failed to lock buffer file: {0}")]
+    Lock(io::Error),
+}
+
+/// Byte of the file that the writer locks. Reader `i` locks byte `i + 1`.
+const WRITER_LOCK: usize = 0;
+
+/// Bit of [Position::attached] that is set, while attached. The other bits
+/// count the attachments, to not detach a position that was attached again.
+const ATTACHED: u32 = 1;
+
+#[repr(C, align(64))]
+struct Position {
+    offset: AtomicU64,
+    attached: AtomicU32,
+}
+
+/// State in the header page of the file.
+///
+/// Offsets are monotonic item counters. The position in the buffer is the
+/// offset modulo the capacity.
+#[repr(C)]
+struct State {
+    writer: Position,
+    readers: [Position; MAX_READERS],
+}
+
+impl Position {
+    /// Mark the position as attached. The caller has to hold its lock.
+    fn attach(&self) {
+        let _ = self
+            .attached
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |a| {
+                Some((a | ATTACHED).wrapping_add(2))
+            });
+    }
+
+    fn detach(&self) {
+        self.attached.fetch_and(!ATTACHED, Ordering::SeqCst);
+    }
+
+    fn attached(&self) -> bool {
+        self.attached.load(Ordering::SeqCst) & ATTACHED != 0
+    }
+
+    /// Detach the position, if it is attached, but its lock is not held.
+    fn reap(&self, fd: BorrowedFd<'_>, byte: usize) -> bool {
+        let a = self.attached.load(Ordering::SeqCst);
+        a & ATTACHED != 0
+            && !locked(fd, byte)
+            // fails, if the position was attached again in the meantime
+            && self
+                .attached
+                .compare_exchange(a, a & !ATTACHED, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+    }
+}
+
+fn fd<T>(buffer: &DoubleMappedBuffer<T>) -> BorrowedFd<'_> {
+    buffer.fd().expect("file-backed buffers keep their fd")
+}
+
+fn state<T>(buffer: &DoubleMappedBuffer<T>) -> Result<*const State, PersistentError> {
+    match buffer.user_header() {
+        Some((addr, len)) if len >= mem::size_of::<State>() => {
+            debug_assert_eq!(addr % mem::align_of::<State>(), 0);
+            Ok(addr as *const State)
+        }
+        _ => Err(PersistentError::Header),
+    }
+}
+
+/// Writer for a persistent circular buffer with items of type `T`.
+pub struct Writer<T> {
+    last_space: usize,
+    state: *const State,
+    buffer: DoubleMappedBuffer<T>,
+}
+
+unsafe impl<T: Send> Send for Writer<T> {}
+
+impl<T: Pod> Writer<T> {
+    /// Open the buffer in the file at `path` or create the file with a buffer
+    /// that can hold at least `min_items` items of type `T`.
+    ///
+    /// If the file exists, `min_items` is ignored and the writer continues at
+    /// the offset, where the last writer stopped.
+    pub fn open(path: impl AsRef<Path>, min_items: usize) -> Result<Self, PersistentError> {
+        Self::with_options(path, min_items, Options::default())
+    }
+
+    /// Open or create the buffer in the file at `path`, with the underlying
+    /// buffer configured through [Options].
+    pub fn with_options(
+        path: impl AsRef<Path>,
+        min_items: usize,
+        options: Options,
+    ) -> Result<Self, PersistentError> {
+        let buffer = DoubleMappedBuffer::open_file(path, min_items, options)?;
+        let state = state(&buffer)?;
+        if !try_lock(fd(&buffer), WRITER_LOCK).map_err(PersistentError::Lock)? {
+            return Err(PersistentError::WriterActive);
+        }
+        unsafe { (*state).writer.attach() };
+
+        Ok(Writer {
+            last_space: 0,
+            state,
+            buffer,
+        })
+    }
+
+    fn state(&self) -> &State {
+        unsafe { &*self.state }
+    }
+
+    fn space_and_offset(&self) -> (usize, u64) {
+        let state = self.state();
+        let capacity = self.buffer.capacity() as u64;
+        let w_off = state.writer.offset.load(Ordering::SeqCst);
+
+        let mut space = capacity;
+        for r in state.readers.iter().filter(|r| r.attached()) {
+            let r_off = r.offset.load(Ordering::SeqCst);
+            space = std::cmp::min(space, capacity.saturating_sub(w_off.wrapping_sub(r_off)));
+        }
+
+        (space as usize, w_off)
+    }
+
+    /// Detach readers whose process terminated without detaching, also before
+    /// a reboot.
+    ///
+    /// Their offset is kept, allowing them to resume. Returns the number of
+    /// detached readers. This is done automatically, when there is no space
+    /// available.
+    pub fn reap_dead_readers(&self) -> usize {
+        let fd = fd(&self.buffer);
+        self.state()
+            .readers
+            .iter()
+            .enumerate()
+            .filter(|(i, r)| r.reap(fd, i + 1))
+            .count()
+    }
+
+    /// Number of attached readers.
+    pub fn readers(&self) -> usize {
+        self.state().readers.iter().filter(|r| r.attached()).count()
+    }
+
+    /// Offset of the writer, i.e., the number of items that were written to the
+    /// buffer since it was created.
+    pub fn offset(&self) -> u64 {
+        self.state().writer.offset.load(Ordering::SeqCst)
+    }
+
+    /// Get a slice to the free slots, available for writing.
+    ///
+    /// This function return immediately. The slice might be [empty](slice::is_empty).
+    pub fn try_slice(&mut self) -> &mut [T] {
+        if self.space_and_offset().0 == 0 {
+            self.reap_dead_readers();
+        }
+        let (space, offset) = self.space_and_offset();
+        self.last_space = space;
+        let offset = (offset % self.buffer.capacity() as u64) as usize;
+        unsafe { &mut self.buffer.slice_with_offset_mut(offset)[0..space] }
+    }
+
+    /// Indicates that `n` items were written to the output buffer.
+    ///
+    /// It is ok if `n` is zero.
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    pub fn produce(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+
+        assert!(n <= self.last_space, "vmcircbuffer: produced too much");
+        self.last_space -= n;
+
+        let state = self.state();
+        let offset = state.writer.offset.load(Ordering::SeqCst);
+        unsafe {
+            self.buffer
+                .mirror((offset % self.buffer.capacity() as u64) as usize, n)
+        };
+        state.writer.offset.fetch_add(n as u64, Ordering::SeqCst);
+    }
+
+    /// Write items and offsets to disk and wait until this is completed.
+    ///
+    /// The items are written before the offsets, i.e., after a crash, the
+    /// offsets on disk never refer to items that were not written.
+    pub fn flush(&self) -> Result<(), PersistentError> {
+        Ok(self.buffer.flush()?)
+    }
+
+    /// Initiate writing items and offsets to disk, without waiting for
+    /// completion.
+    ///
+    /// Contrary to [flush](Writer::flush), the order in which items and
+    /// offsets reach the disk is not defined.
+    pub fn flush_async(&self) -> Result<(), PersistentError> {
+        Ok(self.buffer.flush_async()?)
+    }
+}
+
+impl<T> Drop for Writer<T> {
+    fn drop(&mut self) {
+        unsafe { (*self.state).writer.detach() };
+        if let Some(fd) = self.buffer.fd() {
+            unlock(fd, WRITER_LOCK);
+        }
+    }
+}
+
+/// Reader for a persistent circular buffer with items of type `T`.
+pub struct Reader<T> {
+    index: usize,
+    last_space: usize,
+    state: *const State,
+    buffer: DoubleMappedBuffer<T>,
+}
+
+unsafe impl<T: Send> Send for Reader<T> {}
+
+impl<T: Pod> Reader<T> {
+    /// Open the buffer in the file at `path` as reader with the given `index`.
+    ///
+    /// The reader resumes at the offset, where the last reader with this index
+    /// stopped consuming or, if this is not available anymore, at the oldest
+    /// item in the buffer. The index has to be smaller than [MAX_READERS].
+    pub fn open(path: impl AsRef<Path>, index: usize) -> Result<Self, PersistentError> {
+        if index >= MAX_READERS {
+            return Err(PersistentError::InvalidReader);
+        }
+
+        let buffer = DoubleMappedBuffer::open_existing_file(path, Options::default())?;
+        let state = unsafe { &*state(&buffer)? };
+        if !try_lock(fd(&buffer), index + 1).map_err(PersistentError::Lock)? {
+            return Err(PersistentError::ReaderActive);
+        }
+        state.readers[index].attach();
+
+        let reader = Reader {
+            index,
+            last_space: 0,
+            state,
+            buffer,
+        };
+        reader.skip_overwritten();
+        // the writer might have produced before it saw the reader
+        reader.skip_overwritten();
+
+        Ok(reader)
+    }
+
+    fn state(&self) -> &State {
+        unsafe { &*self.state }
+    }
+
+    /// Move the offset forward to the oldest item that is still in the buffer.
+    fn skip_overwritten(&self) {
+        let state = self.state();
+        let capacity = self.buffer.capacity() as u64;
+        let w_off = state.writer.offset.load(Ordering::SeqCst);
+        let r = &state.readers[self.index];
+        let r_off = r.offset.load(Ordering::SeqCst);
+        if r_off > w_off || w_off - r_off > capacity {
+            r.offset
+                .store(w_off.saturating_sub(capacity), Ordering::SeqCst);
+        }
+    }
+
+    /// Offset of the reader, i.e., the number of items that were consumed since
+    /// the buffer was created, including items that were skipped.
+    pub fn offset(&self) -> u64 {
+        self.state().readers[self.index]
+            .offset
+            .load(Ordering::SeqCst)
+    }
+
+    /// Checks if there is data to read.
+    ///
+    /// If all data is read and no writer is attached, `None` is returned.
+    /// Otherwise, `Some` is returned with a slice that might be empty.
+    pub fn try_slice(&mut self) -> Option<&[T]> {
+        let state = self.state();
+        let r_off = state.readers[self.index].offset.load(Ordering::SeqCst);
+        let mut w_off = state.writer.offset.load(Ordering::SeqCst);
+        let mut gone = false;
+        // the lock is only checked without data, to save the system call
+        if w_off == r_off {
+            gone = !state.writer.attached() || !locked(fd(&self.buffer), WRITER_LOCK);
+            // the writer might have produced before it detached
+            w_off = state.writer.offset.load(Ordering::SeqCst);
+        }
+        let space = w_off.wrapping_sub(r_off) as usize;
+
+        self.last_space = space;
+        if space == 0 && gone {
+            None
+        } else {
+            let offset = (r_off % self.buffer.capacity() as u64) as usize;
+            unsafe { Some(&self.buffer.slice_with_offset(offset)[0..space]) }
+        }
+    }
+
+    /// Indicates that `n` items were read.
+    ///
+    /// # Panics
+    ///
+    /// If consumed more than space was available in the last provided slice.
+    pub fn consume(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+
+        assert!(n <= self.last_space, "vmcircbuffer: consumed too much!");
+        self.last_space -= n;
+
+        self.state().readers[self.index]
+            .offset
+            .fetch_add(n as u64, Ordering::SeqCst);
+    }
+
+    /// Write the offset of the reader to disk and wait until this is completed.
+    pub fn flush(&self) -> Result<(), PersistentError> {
+        Ok(self.buffer.flush()?)
+    }
+}
+
+impl<T> Drop for Reader<T> {
+    fn drop(&mut self) {
+        unsafe { (*self.state).readers[self.index].detach() };
+        if let Some(fd) = self.buffer.fd() {
+            unlock(fd, self.index + 1);
+        }
+    }
+}
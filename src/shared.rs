@@ -0,0 +1,502 @@
+//! Circular Buffer that is shared between processes.
+//!
+//! The [Writer](crate::shared::Writer) creates a
+//! [named](crate::double_mapped_buffer::DoubleMappedBuffer::create_named)
+//! buffer, which [Readers](crate::shared::Reader) in other processes open by
+//! name. All state (writer offset, reader offsets, reader registration) lives
+//! in the header page of the shared mapping and is updated with atomics.
+//! Readers and writer wait for each other with futexes.
+//!
+//! There is a fixed number of [reader slots](crate::shared::MAX_READERS).
+//! Readers register in a free slot and hold a lock on a byte of the buffer file
+//! that belongs to the slot, as long as they are registered. The writer holds
+//! a lock on a byte of its own. These are open file description locks, which
+//! the kernel releases, when the process terminates, independent of PID
+//! namespaces and PID reuse. If a reader process crashes without
+//! unregistering, the writer detects this, once it is blocked by the reader,
+//! and frees the slot. Readers, in turn, treat a crashed writer like a dropped
+//! writer.
+//!
+//! Only available on Linux.
+
+use std::io;
+use std::mem;
+use std::os::unix::io::BorrowedFd;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::double_mapped_buffer::DoubleMappedBuffer;
+use crate::double_mapped_buffer::DoubleMappedBufferError;
+use crate::double_mapped_buffer::Options;
+use crate::double_mapped_buffer::Pod;
+use crate::file_lock::{locked, try_lock, unlock};
+
+/// Maximum number of readers of a shared buffer.
+pub const MAX_READERS: usize = 32;
+
+/// How long to block before checking if the peer process is still alive.
+const LIVENESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// The state of a slot is stored in the lower bits of the slot word, the upper
+// bits count how often the slot was claimed.
+const SLOT_FREE: u32 = 0;
+const SLOT_CLAIMED: u32 = 1;
+const SLOT_ACTIVE: u32 = 2;
+const SLOT_STATE: u32 = 3;
+const SLOT_GENERATION: u32 = 4;
+
+/// Byte of the buffer file that is locked by the writer. Reader slot `i` uses
+/// byte `i + 1`.
+const WRITER_LOCK: usize = 0;
+
+/// Errors setting up a shared buffer.
+#[derive(Error, Debug)]
+pub enum SharedError {
+    /// Failed to create or open the underlying buffer.
+    #[error("Failed to set up double mapped buffer.")]
+    Buffer(#[from] DoubleMappedBufferError),
+    /// The header page cannot hold the shared state.
+    #[error("Header page too small for shared state.")]
+    Header,
+    /// All reader slots are in use.
+    #[error("No free reader slot.")]
+    NoReaderSlot,
+    /// Failed to lock or probe the buffer file.
+    #[error("Failed to lock buffer file: {0}")]
+    Lock(io::Error),
+}
+
+#[repr(C, align(64))]
+struct ReaderSlot {
+    state: AtomicU32,
+    offset: AtomicU64,
+}
+
+#[repr(C, align(64))]
+struct WriterState {
+    offset: AtomicU64,
+    done: AtomicU32,
+}
+
+#[repr(C, align(64))]
+struct Wakeup {
+    seq: AtomicU32,
+    waiters: AtomicU32,
+}
+
+/// State in the header page of the shared mapping.
+///
+/// Offsets are monotonic item counters. The position in the buffer is the
+/// offset modulo the capacity.
+#[repr(C)]
+struct State {
+    writer: WriterState,
+    data: Wakeup,
+    space: Wakeup,
+    readers: [ReaderSlot; MAX_READERS],
+}
+
+impl Wakeup {
+    /// Block until [notify](Wakeup::notify) is called or the timeout expires.
+    ///
+    /// `ready` is evaluated after registering as waiter, which avoids lost
+    /// wakeups.
+    fn wait(&self, timeout: Duration, mut ready: impl FnMut() -> bool) {
+        let seq = self.seq.load(Ordering::SeqCst);
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        if !ready() {
+            futex_wait(&self.seq, seq, timeout);
+        }
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn notify(&self) {
+        self.seq.fetch_add(1, Ordering::SeqCst);
+        if self.waiters.load(Ordering::SeqCst) > 0 {
+            futex_wake(&self.seq);
+        }
+    }
+}
+
+fn futex_wait(word: &AtomicU32, expected: u32, timeout: Duration) {
+    let ts = libc::timespec {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_nsec: timeout.subsec_nanos() as libc::c_long,
+    };
+    unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            word as *const AtomicU32,
+            libc::FUTEX_WAIT,
+            expected,
+            &ts as *const libc::timespec,
+            std::ptr::null::<u32>(),
+            0,
+        );
+    }
+}
+
+fn futex_wake(word: &AtomicU32) {
+    unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            word as *const AtomicU32,
+            libc::FUTEX_WAKE,
+            i32::MAX,
+            std::ptr::null::<libc::timespec>(),
+            std::ptr::null::<u32>(),
+            0,
+        );
+    }
+}
+
+fn fd<T>(buffer: &DoubleMappedBuffer<T>) -> Result<BorrowedFd<'_>, SharedError> {
+    buffer
+        .fd()
+        .ok_or_else(|| SharedError::Lock(io::ErrorKind::Unsupported.into()))
+}
+
+fn state<T>(buffer: &DoubleMappedBuffer<T>) -> Result<*const State, SharedError> {
+    match buffer.user_header() {
+        Some((addr, len)) if len >= mem::size_of::<State>() => {
+            debug_assert_eq!(addr % mem::align_of::<State>(), 0);
+            Ok(addr as *const State)
+        }
+        _ => Err(SharedError::Header),
+    }
+}
+
+/// Writer for a shared circular buffer with items of type `T`.
+pub struct Writer<T> {
+    last_space: usize,
+    state: *const State,
+    buffer: DoubleMappedBuffer<T>,
+}
+
+unsafe impl<T: Send> Send for Writer<T> {}
+
+impl<T: Pod> Writer<T> {
+    /// Create a shared buffer under `name` that can hold at least `min_items`
+    /// items of type `T`.
+    ///
+    /// The name is removed, when the writer is dropped.
+    pub fn create(name: &str, min_items: usize) -> Result<Self, SharedError> {
+        Self::with_options(name, min_items, Options::default())
+    }
+
+    /// Create a shared buffer under `name` that can hold at least `min_items`
+    /// items of type `T`, with the underlying buffer configured through
+    /// [Options].
+    pub fn with_options(
+        name: &str,
+        min_items: usize,
+        options: Options,
+    ) -> Result<Self, SharedError> {
+        let buffer = DoubleMappedBuffer::create_named(name, min_items, options)?;
+        let state = state(&buffer)?;
+        if !try_lock(fd(&buffer)?, WRITER_LOCK).map_err(SharedError::Lock)? {
+            return Err(SharedError::Lock(io::ErrorKind::WouldBlock.into()));
+        }
+
+        Ok(Writer {
+            last_space: 0,
+            state,
+            buffer,
+        })
+    }
+
+    fn state(&self) -> &State {
+        unsafe { &*self.state }
+    }
+
+    fn space_and_offset(&self) -> (usize, u64) {
+        let state = self.state();
+        let capacity = self.buffer.capacity() as u64;
+        let w_off = state.writer.offset.load(Ordering::SeqCst);
+
+        let mut space = capacity;
+        for r in state.readers.iter() {
+            if r.state.load(Ordering::SeqCst) & SLOT_STATE != SLOT_ACTIVE {
+                continue;
+            }
+            let r_off = r.offset.load(Ordering::SeqCst);
+            space = std::cmp::min(space, capacity.saturating_sub(w_off.wrapping_sub(r_off)));
+        }
+
+        (space as usize, w_off)
+    }
+
+    /// Free the slots of readers whose process terminated without
+    /// unregistering, including readers that crashed while registering.
+    ///
+    /// Returns the number of freed slots. This is done automatically, while the
+    /// writer is blocked.
+    pub fn reap_dead_readers(&self) -> usize {
+        let Some(fd) = self.buffer.fd() else {
+            return 0;
+        };
+        let mut n = 0;
+        for (i, r) in self.state().readers.iter().enumerate() {
+            let word = r.state.load(Ordering::SeqCst);
+            // a new reader of the slot changes the generation, i.e., the
+            // exchange fails, if the slot was claimed after the probe
+            if word & SLOT_STATE != SLOT_FREE
+                && !locked(fd, i + 1)
+                && r.state
+                    .compare_exchange(
+                        word,
+                        (word & !SLOT_STATE) | SLOT_FREE,
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    )
+                    .is_ok()
+            {
+                n += 1;
+            }
+        }
+        n
+    }
+
+    /// Number of registered readers.
+    pub fn readers(&self) -> usize {
+        self.state()
+            .readers
+            .iter()
+            .filter(|r| r.state.load(Ordering::SeqCst) & SLOT_STATE == SLOT_ACTIVE)
+            .count()
+    }
+
+    /// Blocking call to get a slice to the available output space.
+    ///
+    /// The function returns as soon as any output space is available.
+    /// The returned slice will never be empty.
+    pub fn slice(&mut self) -> &mut [T] {
+        while self.space_and_offset().0 == 0 {
+            self.state()
+                .space
+                .wait(LIVENESS_INTERVAL, || self.space_and_offset().0 > 0);
+            if self.space_and_offset().0 == 0 {
+                self.reap_dead_readers();
+            }
+        }
+        self.try_slice()
+    }
+
+    /// Get a slice to the free slots, available for writing.
+    ///
+    /// This function return immediately. The slice might be [empty](slice::is_empty).
+    pub fn try_slice(&mut self) -> &mut [T] {
+        let (space, offset) = self.space_and_offset();
+        self.last_space = space;
+        let offset = (offset % self.buffer.capacity() as u64) as usize;
+        unsafe { &mut self.buffer.slice_with_offset_mut(offset)[0..space] }
+    }
+
+    /// Indicates that `n` items were written to the output buffer.
+    ///
+    /// It is ok if `n` is zero.
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    pub fn produce(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+
+        assert!(n <= self.last_space, "vmcircbuffer: produced too much");
+        self.last_space -= n;
+
+        let state = self.state();
+        state.writer.offset.fetch_add(n as u64, Ordering::SeqCst);
+        state.data.notify();
+    }
+}
+
+impl<T> Drop for Writer<T> {
+    fn drop(&mut self) {
+        let state = unsafe { &*self.state };
+        state.writer.done.store(1, Ordering::SeqCst);
+        state.data.notify();
+        if let Some(fd) = self.buffer.fd() {
+            unlock(fd, WRITER_LOCK);
+        }
+    }
+}
+
+/// Reader for a shared circular buffer with items of type `T`.
+pub struct Reader<T> {
+    slot: usize,
+    last_space: usize,
+    state: *const State,
+    buffer: DoubleMappedBuffer<T>,
+}
+
+unsafe impl<T: Send> Send for Reader<T> {}
+
+impl<T: Pod> Reader<T> {
+    /// Open the shared buffer `name` and register as reader.
+    ///
+    /// The reader starts at the current offset of the writer.
+    pub fn open(name: &str) -> Result<Self, SharedError> {
+        let buffer = DoubleMappedBuffer::open_named(name)?;
+        let state = unsafe { &*state(&buffer)? };
+        let fd = fd(&buffer)?;
+
+        // the lock of a slot is only held by its reader, i.e., a slot that can
+        // be locked is free or belongs to a crashed reader
+        let mut slot = None;
+        for i in 0..MAX_READERS {
+            if try_lock(fd, i + 1).map_err(SharedError::Lock)? {
+                slot = Some(i);
+                break;
+            }
+        }
+        let slot = slot.ok_or(SharedError::NoReaderSlot)?;
+
+        let r = &state.readers[slot];
+        // only the writer, reaping the slot, competes
+        let mut word = r.state.load(Ordering::SeqCst);
+        let generation = loop {
+            let generation = (word & !SLOT_STATE).wrapping_add(SLOT_GENERATION);
+            match r.state.compare_exchange(
+                word,
+                generation | SLOT_CLAIMED,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => break generation,
+                Err(w) => word = w,
+            }
+        };
+        r.offset
+            .store(state.writer.offset.load(Ordering::SeqCst), Ordering::SeqCst);
+        r.state.store(generation | SLOT_ACTIVE, Ordering::SeqCst);
+        // the writer might have produced before it saw the slot
+        r.offset
+            .store(state.writer.offset.load(Ordering::SeqCst), Ordering::SeqCst);
+
+        Ok(Reader {
+            slot,
+            last_space: 0,
+            state,
+            buffer,
+        })
+    }
+
+    fn state(&self) -> &State {
+        unsafe { &*self.state }
+    }
+
+    fn space_and_offset_and_done(&self) -> (usize, u64, bool) {
+        let state = self.state();
+        let done = state.writer.done.load(Ordering::SeqCst) != 0;
+        let w_off = state.writer.offset.load(Ordering::SeqCst);
+        let r_off = state.readers[self.slot].offset.load(Ordering::SeqCst);
+        (w_off.wrapping_sub(r_off) as usize, r_off, done)
+    }
+
+    fn writer_gone(&self) -> bool {
+        let writer = &self.state().writer;
+        writer.done.load(Ordering::SeqCst) != 0
+            || self.buffer.fd().is_some_and(|fd| !locked(fd, WRITER_LOCK))
+    }
+
+    /// Blocks until there is data to read or until the writer is dropped.
+    ///
+    /// If all data is read and the writer is dropped or crashed, all following
+    /// calls will return `None`. If `Some` is returned, the contained slice is
+    /// never empty.
+    pub fn slice(&mut self) -> Option<&[T]> {
+        loop {
+            let (space, _, _) = self.space_and_offset_and_done();
+            let gone = self.writer_gone();
+            if space > 0 || gone {
+                return self.slice_or_none(gone);
+            }
+            self.state().data.wait(LIVENESS_INTERVAL, || {
+                let (space, _, done) = self.space_and_offset_and_done();
+                space > 0 || done
+            });
+        }
+    }
+
+    /// Checks if there is data to read.
+    ///
+    /// If all data is read and the writer is dropped or crashed, all following
+    /// calls will return `None`. If there is no data to read, `Some` is
+    /// returned with an empty slice.
+    pub fn try_slice(&mut self) -> Option<&[T]> {
+        let gone = self.writer_gone();
+        self.slice_or_none(gone)
+    }
+
+    fn slice_or_none(&mut self, gone: bool) -> Option<&[T]> {
+        let (space, offset, _) = self.space_and_offset_and_done();
+        self.last_space = space;
+        if space == 0 && gone {
+            None
+        } else {
+            let offset = (offset % self.buffer.capacity() as u64) as usize;
+            unsafe { Some(&self.buffer.slice_with_offset(offset)[0..space]) }
+        }
+    }
+
+    /// Indicates that `n` items were read.
+    ///
+    /// # Panics
+    ///
+    /// If consumed more than space was available in the last provided slice.
+    pub fn consume(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+
+        assert!(n <= self.last_space, "vmcircbuffer: consumed too much!");
+        self.last_space -= n;
+
+        let state = self.state();
+        state.readers[self.slot]
+            .offset
+            .fetch_add(n as u64, Ordering::SeqCst);
+        state.space.notify();
+    }
+}
+
+impl<T> Drop for Reader<T> {
+    fn drop(&mut self) {
+        let state = unsafe { &*self.state };
+        let r = &state.readers[self.slot];
+        let word = r.state.load(Ordering::SeqCst);
+        r.state
+            .store((word & !SLOT_STATE) | SLOT_FREE, Ordering::SeqCst);
+        state.space.notify();
+        if let Some(fd) = self.buffer.fd() {
+            unlock(fd, self.slot + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reap_claimed() {
+        let name = format!("vmcircbuffer-shared-reap-claimed-{}", std::process::id());
+        let w = Writer::<u32>::create(&name, 1).unwrap();
+        let r = Reader::<u32>::open(&name).unwrap();
+
+        // reader that crashed while registering, i.e., without holding the lock
+        let slot = &w.state().readers[r.slot + 1];
+        slot.state
+            .store(SLOT_GENERATION | SLOT_CLAIMED, Ordering::SeqCst);
+
+        assert_eq!(w.reap_dead_readers(), 1);
+        assert_eq!(slot.state.load(Ordering::SeqCst) & SLOT_STATE, SLOT_FREE);
+        assert_eq!(w.readers(), 1);
+        drop(r);
+        assert_eq!(w.reap_dead_readers(), 0);
+    }
+}
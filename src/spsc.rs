@@ -0,0 +1,330 @@
+//! Single-producer/single-consumer circular buffer.
+//!
+//! A buffer with exactly one [Writer] and one [Reader], which are created
+//! together. In contrast to the [generic](crate::generic) buffer, it does not
+//! support metadata, more readers, or resizing. In turn, the writer and the
+//! reader only exchange their positions through atomics, i.e., the fast path
+//! is lock-free and does not allocate. Only arming a [Notifier] and notifying
+//! an armed one take a lock.
+//!
+//! Like the other buffers, it comes with [sync], [async](asynchronous), and
+//! [non-blocking](nonblocking) flavours. This module provides the variant
+//! with a custom [Notifier].
+
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::double_mapped_buffer::assume_init_mut;
+use crate::double_mapped_buffer::Backend;
+use crate::double_mapped_buffer::DefaultBackend;
+use crate::double_mapped_buffer::DoubleMappedBuffer;
+use crate::double_mapped_buffer::Options;
+use crate::double_mapped_buffer::Pod;
+use crate::generic::CachePadded;
+use crate::generic::CircularError;
+use crate::generic::Notifier;
+use crate::generic::Notify;
+
+#[cfg(feature = "async")]
+pub mod asynchronous;
+#[cfg(feature = "nonblocking")]
+pub mod nonblocking;
+#[cfg(feature = "sync")]
+pub mod sync;
+
+/// Single-producer/single-consumer Circular Buffer Constructor
+pub struct Circular;
+
+#[allow(clippy::type_complexity)]
+impl Circular {
+    /// Create a buffer that can hold at least `min_items` items of type `T`.
+    ///
+    /// The size is the least common multiple of the page size and the size of `T`.
+    pub fn with_capacity<T, N>(
+        min_items: usize,
+        reader_notifier: N,
+        writer_notifier: N,
+    ) -> Result<(Writer<T, N>, Reader<T, N>), CircularError>
+    where
+        N: Notifier,
+    {
+        Self::with_options(
+            min_items,
+            Options::default(),
+            reader_notifier,
+            writer_notifier,
+        )
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with the underlying [DoubleMappedBuffer] configured through [Options].
+    pub fn with_options<T, N>(
+        min_items: usize,
+        options: Options,
+        reader_notifier: N,
+        writer_notifier: N,
+    ) -> Result<(Writer<T, N>, Reader<T, N>), CircularError>
+    where
+        N: Notifier,
+    {
+        Self::with_backend::<T, DefaultBackend, N>(
+            min_items,
+            options,
+            reader_notifier,
+            writer_notifier,
+        )
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with memory that is provided by [Backend] `B`.
+    pub fn with_backend<T, B, N>(
+        min_items: usize,
+        options: Options,
+        reader_notifier: N,
+        writer_notifier: N,
+    ) -> Result<(Writer<T, N>, Reader<T, N>), CircularError>
+    where
+        B: Backend,
+        N: Notifier,
+    {
+        let buffer = DoubleMappedBuffer::new_in::<B>(min_items, options.clone())
+            .map_err(|e| CircularError::allocation::<T>(e, min_items, &options))?;
+        Self::with_buffer(buffer, reader_notifier, writer_notifier)
+    }
+
+    /// Create a circular buffer on top of an existing [DoubleMappedBuffer].
+    pub fn with_buffer<T, N>(
+        buffer: DoubleMappedBuffer<T>,
+        reader_notifier: N,
+        writer_notifier: N,
+    ) -> Result<(Writer<T, N>, Reader<T, N>), CircularError>
+    where
+        N: Notifier,
+    {
+        if buffer.is_read_only() {
+            return Err(CircularError::ReadOnly);
+        }
+        let capacity = buffer.capacity();
+
+        let state = Arc::new(State {
+            writer: CachePadded(AtomicU64::new(0)),
+            reader: CachePadded(AtomicU64::new(0)),
+            writer_done: AtomicBool::new(false),
+            reader_done: AtomicBool::new(false),
+            reader_notifier: Notify::new(reader_notifier),
+            writer_notifier: Notify::new(writer_notifier),
+            buffer,
+        });
+
+        let writer = Writer {
+            position: 0,
+            capacity,
+            last_space: 0,
+            state: state.clone(),
+        };
+        let reader = Reader {
+            position: 0,
+            capacity,
+            last_space: 0,
+            state,
+        };
+
+        Ok((writer, reader))
+    }
+}
+
+/// State that is shared between the writer and the reader.
+///
+/// Positions are monotonic counters of items. The offset in the buffer is the
+/// position modulo the capacity.
+struct State<T, N> {
+    /// Position of the writer.
+    writer: CachePadded<AtomicU64>,
+    /// Position of the reader.
+    reader: CachePadded<AtomicU64>,
+    writer_done: AtomicBool,
+    reader_done: AtomicBool,
+    reader_notifier: Notify<N>,
+    writer_notifier: Notify<N>,
+    buffer: DoubleMappedBuffer<T>,
+}
+
+/// Writer for a single-producer/single-consumer circular buffer with items
+/// of type `T` and [Notifier] of type `N`.
+pub struct Writer<T, N>
+where
+    N: Notifier,
+{
+    /// Position of the writer, which is only updated by the writer.
+    position: u64,
+    capacity: usize,
+    last_space: usize,
+    state: Arc<State<T, N>>,
+}
+
+impl<T, N> Writer<T, N>
+where
+    N: Notifier,
+{
+    fn space(&self) -> usize {
+        let reader = self.state.reader.load(Ordering::SeqCst);
+        if self.state.reader_done.load(Ordering::Relaxed) {
+            // nobody reads the items anymore
+            return self.capacity;
+        }
+        self.capacity - (self.position - reader) as usize
+    }
+
+    /// Get a slice for the output buffer space. Might be empty.
+    ///
+    /// The slice holds old items or, if nothing was written yet, zeros. This is
+    /// only safe for [Pod] types. Other types have to use
+    /// [slice_uninit](Writer::slice_uninit).
+    pub fn slice(&mut self, arm: bool) -> &mut [T]
+    where
+        T: Pod,
+    {
+        unsafe { assume_init_mut(self.slice_uninit(arm)) }
+    }
+
+    /// Get a slice for the output buffer space as possibly uninitialized
+    /// items. Might be empty.
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::slice_uninit](crate::generic::Writer::slice_uninit).
+    pub unsafe fn slice_uninit(&mut self, arm: bool) -> &mut [MaybeUninit<T>] {
+        let mut space = self.space();
+        if space == 0 && arm {
+            self.state.writer_notifier.arm();
+            space = self.space();
+        }
+        self.last_space = space;
+
+        let offset = (self.position % self.capacity as u64) as usize;
+        &mut self.state.buffer.slice_with_offset_uninit(offset)[0..space]
+    }
+
+    /// Indicates that `n` items were written to the output buffer.
+    ///
+    /// It is ok if `n` is zero.
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    pub fn produce(&mut self, n: usize)
+    where
+        T: Pod,
+    {
+        unsafe { self.assume_init(n) }
+    }
+
+    /// Indicates that the first `n` items of the last provided slice were
+    /// initialized and hands them to the reader, like [produce](Writer::produce).
+    ///
+    /// # Safety
+    ///
+    /// The first `n` items of the last provided slice have to be initialized.
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    pub unsafe fn assume_init(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+
+        assert!(n <= self.last_space, "vmcircbuffer: produced too much");
+        self.last_space -= n;
+
+        let offset = (self.position % self.capacity as u64) as usize;
+        unsafe { self.state.buffer.mirror(offset, n) };
+
+        self.position += n as u64;
+        self.state.writer.store(self.position, Ordering::SeqCst);
+        self.state.reader_notifier.notify();
+    }
+}
+
+impl<T, N> Drop for Writer<T, N>
+where
+    N: Notifier,
+{
+    fn drop(&mut self) {
+        self.state.writer_done.store(true, Ordering::SeqCst);
+        self.state.reader_notifier.notify();
+    }
+}
+
+/// Reader for a single-producer/single-consumer circular buffer with items
+/// of type `T` and [Notifier] of type `N`.
+pub struct Reader<T, N>
+where
+    N: Notifier,
+{
+    /// Position of the reader, which is only updated by the reader.
+    position: u64,
+    capacity: usize,
+    last_space: usize,
+    state: Arc<State<T, N>>,
+}
+
+impl<T, N> Reader<T, N>
+where
+    N: Notifier,
+{
+    /// Number of available items and whether the writer is done.
+    fn space(&self) -> (usize, bool) {
+        let done = self.state.writer_done.load(Ordering::SeqCst);
+        let writer = self.state.writer.load(Ordering::SeqCst);
+        ((writer - self.position) as usize, done)
+    }
+
+    /// Get a slice with the items available to read.
+    ///
+    /// Returns `None` if the writer was dropped and all data was read.
+    pub fn slice(&mut self, arm: bool) -> Option<&[T]> {
+        let (mut space, mut done) = self.space();
+        if space == 0 && arm {
+            self.state.reader_notifier.arm();
+            (space, done) = self.space();
+        }
+
+        self.last_space = space;
+        if space == 0 && done {
+            None
+        } else {
+            let offset = (self.position % self.capacity as u64) as usize;
+            unsafe { Some(&self.state.buffer.slice_with_offset(offset)[0..space]) }
+        }
+    }
+
+    /// Indicates that `n` items were read.
+    ///
+    /// # Panics
+    ///
+    /// If consumed more than space was available in the last provided slice.
+    pub fn consume(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+
+        assert!(n <= self.last_space, "vmcircbuffer: consumed too much!");
+        self.last_space -= n;
+
+        self.position += n as u64;
+        self.state.reader.store(self.position, Ordering::SeqCst);
+        self.state.writer_notifier.notify();
+    }
+}
+
+impl<T, N> Drop for Reader<T, N>
+where
+    N: Notifier,
+{
+    fn drop(&mut self) {
+        self.state.reader_done.store(true, Ordering::SeqCst);
+        self.state.writer_notifier.notify();
+    }
+}
@@ -0,0 +1,232 @@
+//! Async single-producer/single-consumer buffer that can `await` until data
+//! or buffer space becomes available.
+
+use futures::channel::mpsc::{channel, Receiver};
+use futures::StreamExt;
+use std::mem::MaybeUninit;
+use std::slice;
+
+use crate::asynchronous::AsyncNotifier;
+use crate::double_mapped_buffer::assume_init_mut;
+use crate::double_mapped_buffer::Backend;
+use crate::double_mapped_buffer::DoubleMappedBuffer;
+use crate::double_mapped_buffer::Options;
+use crate::double_mapped_buffer::Pod;
+use crate::generic::CircularError;
+use crate::spsc;
+
+/// Builder for the *async* single-producer/single-consumer buffer.
+pub struct Circular;
+
+impl Circular {
+    /// Create a buffer for items of type `T` with minimal capacity (usually a page size).
+    ///
+    /// The actual size is the least common multiple of the page size and the size of `T`.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<T>() -> Result<(Writer<T>, Reader<T>), CircularError> {
+        Self::with_capacity(0)
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`.
+    ///
+    /// The size is the least common multiple of the page size and the size of `T`.
+    pub fn with_capacity<T>(min_items: usize) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        Self::with_options(min_items, Options::default())
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with the underlying buffer configured through [Options].
+    pub fn with_options<T>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        let (rn, r_chan, wn, w_chan) = notifiers();
+        let (w, r) = spsc::Circular::with_options(min_items, options, rn, wn)?;
+        Ok(wrap(w, w_chan, r, r_chan))
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with memory that is provided by [Backend] `B`.
+    pub fn with_backend<T, B: Backend>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        let (rn, r_chan, wn, w_chan) = notifiers();
+        let (w, r) = spsc::Circular::with_backend::<T, B, _>(min_items, options, rn, wn)?;
+        Ok(wrap(w, w_chan, r, r_chan))
+    }
+
+    /// Create a circular buffer on top of an existing [DoubleMappedBuffer].
+    pub fn with_buffer<T>(
+        buffer: DoubleMappedBuffer<T>,
+    ) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        let (rn, r_chan, wn, w_chan) = notifiers();
+        let (w, r) = spsc::Circular::with_buffer(buffer, rn, wn)?;
+        Ok(wrap(w, w_chan, r, r_chan))
+    }
+}
+
+fn notifiers() -> (AsyncNotifier, Receiver<()>, AsyncNotifier, Receiver<()>) {
+    let (r_tx, r_rx) = channel(1);
+    let (w_tx, w_rx) = channel(1);
+    (
+        AsyncNotifier::new(r_tx),
+        r_rx,
+        AsyncNotifier::new(w_tx),
+        w_rx,
+    )
+}
+
+fn wrap<T>(
+    writer: spsc::Writer<T, AsyncNotifier>,
+    w_chan: Receiver<()>,
+    reader: spsc::Reader<T, AsyncNotifier>,
+    r_chan: Receiver<()>,
+) -> (Writer<T>, Reader<T>) {
+    (
+        Writer {
+            writer,
+            chan: w_chan,
+        },
+        Reader {
+            reader,
+            chan: r_chan,
+        },
+    )
+}
+
+/// Writer for an async single-producer/single-consumer buffer with items of type `T`.
+pub struct Writer<T> {
+    chan: Receiver<()>,
+    writer: spsc::Writer<T, AsyncNotifier>,
+}
+
+impl<T> Writer<T> {
+    /// Get a slice to the available output space.
+    ///
+    /// The future resolves once output space is available.
+    /// The returned slice will never be empty.
+    pub async fn slice(&mut self) -> &mut [T]
+    where
+        T: Pod,
+    {
+        unsafe { assume_init_mut(self.slice_uninit().await) }
+    }
+
+    /// Get a slice to the available output space as possibly uninitialized
+    /// items.
+    ///
+    /// The future resolves once output space is available. The returned slice
+    /// will never be empty.
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::slice_uninit](crate::generic::Writer::slice_uninit).
+    pub async unsafe fn slice_uninit(&mut self) -> &mut [MaybeUninit<T>] {
+        // ugly workaround for borrow-checker problem
+        // https://github.com/rust-lang/rust/issues/21906
+        let (p, s) = loop {
+            match self.writer.slice_uninit(true) {
+                [] => {
+                    let _ = self.chan.next().await;
+                }
+                s => break (s.as_mut_ptr(), s.len()),
+            }
+        };
+        unsafe { slice::from_raw_parts_mut(p, s) }
+    }
+
+    /// Get a slice to the free slots, available for writing.
+    ///
+    /// This function return immediately. The slice might be [empty](slice::is_empty).
+    pub fn try_slice(&mut self) -> &mut [T]
+    where
+        T: Pod,
+    {
+        self.writer.slice(false)
+    }
+
+    /// Get a slice to the free slots as possibly uninitialized items.
+    ///
+    /// This function return immediately. The slice might be [empty](slice::is_empty).
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::slice_uninit](crate::generic::Writer::slice_uninit).
+    pub unsafe fn try_slice_uninit(&mut self) -> &mut [MaybeUninit<T>] {
+        self.writer.slice_uninit(false)
+    }
+
+    /// Indicates that `n` items were written to the output buffer.
+    ///
+    /// It is ok if `n` is zero.
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    pub fn produce(&mut self, n: usize)
+    where
+        T: Pod,
+    {
+        self.writer.produce(n);
+    }
+
+    /// Indicates that the first `n` items of the last provided slice were
+    /// initialized.
+    ///
+    /// # Safety
+    ///
+    /// See [spsc::Writer::assume_init].
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    pub unsafe fn assume_init(&mut self, n: usize) {
+        self.writer.assume_init(n);
+    }
+}
+
+/// Reader for an async single-producer/single-consumer buffer with items of type `T`.
+pub struct Reader<T> {
+    chan: Receiver<()>,
+    reader: spsc::Reader<T, AsyncNotifier>,
+}
+
+impl<T> Reader<T> {
+    /// Waits until there is data to read or until the writer is dropped.
+    ///
+    /// If all data is read and the writer is dropped, all following calls will
+    /// return `None`. If `Some` is returned, the contained slice is never empty.
+    pub async fn slice(&mut self) -> Option<&[T]> {
+        // ugly workaround for borrow-checker problem
+        // https://github.com/rust-lang/rust/issues/21906
+        let r = loop {
+            match self.reader.slice(true) {
+                Some([]) => {
+                    let _ = self.chan.next().await;
+                }
+                Some(s) => break Some((s.as_ptr(), s.len())),
+                None => break None,
+            }
+        };
+        r.map(|(p, s)| unsafe { slice::from_raw_parts(p, s) })
+    }
+
+    /// Checks if there is data to read.
+    ///
+    /// If all data is read and the writer is dropped, all following calls will
+    /// return `None`. If there is no data to read, `Some` is returned with an
+    /// empty slice.
+    pub fn try_slice(&mut self) -> Option<&[T]> {
+        self.reader.slice(false)
+    }
+
+    /// Indicates that `n` items were read.
+    ///
+    /// # Panics
+    ///
+    /// If consumed more than space was available in the last provided slice.
+    pub fn consume(&mut self, n: usize) {
+        self.reader.consume(n);
+    }
+}
@@ -0,0 +1,152 @@
+//! Non-blocking single-producer/single-consumer buffer that can only check if
+//! data or buffer space is available right now.
+
+use std::mem::MaybeUninit;
+
+use crate::double_mapped_buffer::Backend;
+use crate::double_mapped_buffer::DoubleMappedBuffer;
+use crate::double_mapped_buffer::Options;
+use crate::double_mapped_buffer::Pod;
+use crate::generic::CircularError;
+use crate::nonblocking::NullNotifier;
+use crate::spsc;
+
+/// Builder for the *non-blocking* single-producer/single-consumer buffer.
+pub struct Circular;
+
+impl Circular {
+    /// Create a buffer for items of type `T` with minimal capacity (usually a page size).
+    ///
+    /// The actual size is the least common multiple of the page size and the size of `T`.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<T>() -> Result<(Writer<T>, Reader<T>), CircularError> {
+        Self::with_capacity(0)
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`.
+    ///
+    /// The size is the least common multiple of the page size and the size of `T`.
+    pub fn with_capacity<T>(min_items: usize) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        Self::with_options(min_items, Options::default())
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with the underlying buffer configured through [Options].
+    pub fn with_options<T>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        let (w, r) = spsc::Circular::with_options(min_items, options, NullNotifier, NullNotifier)?;
+        Ok((Writer { writer: w }, Reader { reader: r }))
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with memory that is provided by [Backend] `B`.
+    pub fn with_backend<T, B: Backend>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        let (w, r) = spsc::Circular::with_backend::<T, B, _>(
+            min_items,
+            options,
+            NullNotifier,
+            NullNotifier,
+        )?;
+        Ok((Writer { writer: w }, Reader { reader: r }))
+    }
+
+    /// Create a circular buffer on top of an existing [DoubleMappedBuffer].
+    pub fn with_buffer<T>(
+        buffer: DoubleMappedBuffer<T>,
+    ) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        let (w, r) = spsc::Circular::with_buffer(buffer, NullNotifier, NullNotifier)?;
+        Ok((Writer { writer: w }, Reader { reader: r }))
+    }
+}
+
+/// Writer for a non-blocking single-producer/single-consumer buffer with items of type `T`.
+pub struct Writer<T> {
+    writer: spsc::Writer<T, NullNotifier>,
+}
+
+impl<T> Writer<T> {
+    /// Get a slice to the free slots, available for writing.
+    ///
+    /// This function return immediately. The slice might be [empty](slice::is_empty).
+    #[inline]
+    pub fn try_slice(&mut self) -> &mut [T]
+    where
+        T: Pod,
+    {
+        self.writer.slice(false)
+    }
+
+    /// Get a slice to the free slots as possibly uninitialized items.
+    ///
+    /// This function return immediately. The slice might be [empty](slice::is_empty).
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::slice_uninit](crate::generic::Writer::slice_uninit).
+    #[inline]
+    pub unsafe fn try_slice_uninit(&mut self) -> &mut [MaybeUninit<T>] {
+        self.writer.slice_uninit(false)
+    }
+
+    /// Indicates that `n` items were written to the output buffer.
+    ///
+    /// It is ok if `n` is zero.
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    #[inline]
+    pub fn produce(&mut self, n: usize)
+    where
+        T: Pod,
+    {
+        self.writer.produce(n);
+    }
+
+    /// Indicates that the first `n` items of the last provided slice were
+    /// initialized.
+    ///
+    /// # Safety
+    ///
+    /// See [spsc::Writer::assume_init].
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    #[inline]
+    pub unsafe fn assume_init(&mut self, n: usize) {
+        self.writer.assume_init(n);
+    }
+}
+
+/// Reader for a non-blocking single-producer/single-consumer buffer with items of type `T`.
+pub struct Reader<T> {
+    reader: spsc::Reader<T, NullNotifier>,
+}
+
+impl<T> Reader<T> {
+    /// Checks if there is data to read.
+    ///
+    /// If all data is read and the writer is dropped, all following calls will
+    /// return `None`. If there is no data to read, `Some` is returned with an
+    /// empty slice.
+    #[inline]
+    pub fn try_slice(&mut self) -> Option<&[T]> {
+        self.reader.slice(false)
+    }
+
+    /// Indicates that `n` items were read.
+    ///
+    /// # Panics
+    ///
+    /// If consumed more than space was available in the last provided slice.
+    #[inline]
+    pub fn consume(&mut self, n: usize) {
+        self.reader.consume(n);
+    }
+}
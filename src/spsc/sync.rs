@@ -0,0 +1,220 @@
+//! Blocking single-producer/single-consumer buffer that blocks until data or
+//! buffer space becomes available.
+
+use std::mem::MaybeUninit;
+use std::slice;
+use std::thread;
+use std::thread::Thread;
+
+use crate::double_mapped_buffer::assume_init_mut;
+use crate::double_mapped_buffer::Backend;
+use crate::double_mapped_buffer::DoubleMappedBuffer;
+use crate::double_mapped_buffer::Options;
+use crate::double_mapped_buffer::Pod;
+use crate::generic::CircularError;
+use crate::generic::Notifier;
+use crate::spsc;
+
+/// Notifier that unparks the thread that armed it.
+struct ParkNotifier {
+    thread: Option<Thread>,
+}
+
+impl Notifier for ParkNotifier {
+    fn arm(&mut self) {
+        self.thread = Some(thread::current());
+    }
+    fn notify(&mut self) {
+        if let Some(t) = self.thread.take() {
+            t.unpark();
+        }
+    }
+}
+
+/// Builder for the *blocking* single-producer/single-consumer buffer.
+pub struct Circular;
+
+impl Circular {
+    /// Create a buffer for items of type `T` with minimal capacity (usually a page size).
+    ///
+    /// The actual size is the least common multiple of the page size and the size of `T`.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<T>() -> Result<(Writer<T>, Reader<T>), CircularError> {
+        Self::with_capacity(0)
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`.
+    ///
+    /// The size is the least common multiple of the page size and the size of `T`.
+    pub fn with_capacity<T>(min_items: usize) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        Self::with_options(min_items, Options::default())
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with the underlying buffer configured through [Options].
+    pub fn with_options<T>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        let (w, r) = spsc::Circular::with_options(min_items, options, notifier(), notifier())?;
+        Ok((Writer { writer: w }, Reader { reader: r }))
+    }
+
+    /// Create a buffer that can hold at least `min_items` items of type `T`,
+    /// with memory that is provided by [Backend] `B`.
+    pub fn with_backend<T, B: Backend>(
+        min_items: usize,
+        options: Options,
+    ) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        let (w, r) =
+            spsc::Circular::with_backend::<T, B, _>(min_items, options, notifier(), notifier())?;
+        Ok((Writer { writer: w }, Reader { reader: r }))
+    }
+
+    /// Create a circular buffer on top of an existing [DoubleMappedBuffer].
+    pub fn with_buffer<T>(
+        buffer: DoubleMappedBuffer<T>,
+    ) -> Result<(Writer<T>, Reader<T>), CircularError> {
+        let (w, r) = spsc::Circular::with_buffer(buffer, notifier(), notifier())?;
+        Ok((Writer { writer: w }, Reader { reader: r }))
+    }
+}
+
+fn notifier() -> ParkNotifier {
+    ParkNotifier { thread: None }
+}
+
+/// Writer for a blocking single-producer/single-consumer buffer with items of type `T`.
+pub struct Writer<T> {
+    writer: spsc::Writer<T, ParkNotifier>,
+}
+
+impl<T> Writer<T> {
+    /// Blocking call to get a slice to the available output space.
+    ///
+    /// The function returns as soon as any output space is available.
+    /// The returned slice will never be empty.
+    pub fn slice(&mut self) -> &mut [T]
+    where
+        T: Pod,
+    {
+        unsafe { assume_init_mut(self.slice_uninit()) }
+    }
+
+    /// Blocking call to get a slice to the available output space as possibly
+    /// uninitialized items.
+    ///
+    /// The returned slice will never be empty.
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::slice_uninit](crate::generic::Writer::slice_uninit).
+    pub unsafe fn slice_uninit(&mut self) -> &mut [MaybeUninit<T>] {
+        // ugly workaround for borrow-checker problem
+        // https://github.com/rust-lang/rust/issues/21906
+        let (p, s) = loop {
+            match self.writer.slice_uninit(true) {
+                [] => thread::park(),
+                s => break (s.as_mut_ptr(), s.len()),
+            }
+        };
+        unsafe { slice::from_raw_parts_mut(p, s) }
+    }
+
+    /// Get a slice to the free slots, available for writing.
+    ///
+    /// This function return immediately. The slice might be [empty](slice::is_empty).
+    #[inline]
+    pub fn try_slice(&mut self) -> &mut [T]
+    where
+        T: Pod,
+    {
+        self.writer.slice(false)
+    }
+
+    /// Get a slice to the free slots as possibly uninitialized items.
+    ///
+    /// This function return immediately. The slice might be [empty](slice::is_empty).
+    ///
+    /// # Safety
+    ///
+    /// See [generic::Writer::slice_uninit](crate::generic::Writer::slice_uninit).
+    #[inline]
+    pub unsafe fn try_slice_uninit(&mut self) -> &mut [MaybeUninit<T>] {
+        self.writer.slice_uninit(false)
+    }
+
+    /// Indicates that `n` items were written to the output buffer.
+    ///
+    /// It is ok if `n` is zero.
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    #[inline]
+    pub fn produce(&mut self, n: usize)
+    where
+        T: Pod,
+    {
+        self.writer.produce(n);
+    }
+
+    /// Indicates that the first `n` items of the last provided slice were
+    /// initialized.
+    ///
+    /// # Safety
+    ///
+    /// See [spsc::Writer::assume_init].
+    ///
+    /// # Panics
+    ///
+    /// If produced more than space was available in the last provided slice.
+    #[inline]
+    pub unsafe fn assume_init(&mut self, n: usize) {
+        self.writer.assume_init(n);
+    }
+}
+
+/// Reader for a blocking single-producer/single-consumer buffer with items of type `T`.
+pub struct Reader<T> {
+    reader: spsc::Reader<T, ParkNotifier>,
+}
+
+impl<T> Reader<T> {
+    /// Blocks until there is data to read or until the writer is dropped.
+    ///
+    /// If all data is read and the writer is dropped, all following calls will
+    /// return `None`. If `Some` is returned, the contained slice is never empty.
+    pub fn slice(&mut self) -> Option<&[T]> {
+        // ugly workaround for borrow-checker problem
+        // https://github.com/rust-lang/rust/issues/21906
+        let r = loop {
+            match self.reader.slice(true) {
+                Some([]) => thread::park(),
+                Some(s) => break Some((s.as_ptr(), s.len())),
+                None => break None,
+            }
+        };
+        r.map(|(p, s)| unsafe { slice::from_raw_parts(p, s) })
+    }
+
+    /// Checks if there is data to read.
+    ///
+    /// If all data is read and the writer is dropped, all following calls will
+    /// return `None`. If there is no data to read, `Some` is returned with an
+    /// empty slice.
+    #[inline]
+    pub fn try_slice(&mut self) -> Option<&[T]> {
+        self.reader.slice(false)
+    }
+
+    /// Indicates that `n` items were read.
+    ///
+    /// # Panics
+    ///
+    /// If consumed more than space was available in the last provided slice.
+    #[inline]
+    pub fn consume(&mut self, n: usize) {
+        self.reader.consume(n);
+    }
+}
@@ -0,0 +1,129 @@
+use std::any::Any;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use vmcircbuffer::double_mapped_buffer::accounting;
+use vmcircbuffer::double_mapped_buffer::{
+    pagesize, Backend, DoubleMappedBuffer, DoubleMappedBufferError, Options,
+};
+use vmcircbuffer::generic::CircularError;
+use vmcircbuffer::sync::Circular;
+
+/// Backend that fails to allocate and counts the attempts.
+struct FailingBackend;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl Backend for FailingBackend {
+    fn allocate(
+        _min_items: usize,
+        _item_size: usize,
+        _alignment: usize,
+        _options: &Options,
+    ) -> Result<Self, DoubleMappedBufferError> {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        Err(DoubleMappedBufferError::Unsupported)
+    }
+
+    fn addr(&self) -> usize {
+        unreachable!()
+    }
+
+    fn size_bytes(&self) -> usize {
+        unreachable!()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// The accounting is process-wide. All checks are, therefore, done in one test
+// to not interfere with each other.
+#[test]
+fn accounting() {
+    assert_eq!(accounting::total(), 0);
+    assert!(accounting::buffers().is_empty());
+
+    let a = DoubleMappedBuffer::<u32>::with_options(1, Options::new().label("a")).unwrap();
+    let b = DoubleMappedBuffer::<u8>::new(1).unwrap();
+    b.set_label("b");
+
+    let buffers = accounting::buffers();
+    assert_eq!(buffers.len(), 2);
+    assert_eq!(buffers[0].label(), Some("a"));
+    assert_eq!(buffers[0].item(), "u32");
+    assert_eq!(buffers[0].bytes(), a.capacity() * 4);
+    assert_eq!(buffers[0].virtual_bytes(), 2 * a.capacity() * 4);
+    assert_eq!(buffers[1].label(), Some("b"));
+    assert_eq!(b.label().as_deref(), Some("b"));
+    assert_eq!(accounting::total(), a.capacity() * 4 + b.capacity());
+
+    drop(a);
+    assert_eq!(accounting::buffers().len(), 1);
+    assert_eq!(accounting::total(), b.capacity());
+
+    // budget
+    accounting::set_budget(Some(b.capacity() + pagesize()));
+    assert_eq!(accounting::budget(), Some(b.capacity() + pagesize()));
+
+    let w = Circular::with_capacity::<u8>(pagesize()).unwrap();
+    assert!(matches!(
+        Circular::with_capacity::<u8>(1),
+        Err(CircularError::Budget)
+    ));
+    assert_eq!(accounting::buffers().len(), 2);
+
+    drop(w);
+    assert!(Circular::with_capacity::<u8>(1).is_ok());
+
+    // the budget is checked before the memory is allocated
+    assert!(matches!(
+        DoubleMappedBuffer::<u8>::new_in::<FailingBackend>(2 * pagesize(), Options::new()),
+        Err(DoubleMappedBufferError::Budget)
+    ));
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), 0);
+
+    // the reservation is released, if the allocation fails
+    assert!(matches!(
+        DoubleMappedBuffer::<u8>::new_in::<FailingBackend>(1, Options::new()),
+        Err(DoubleMappedBufferError::Unsupported)
+    ));
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), 1);
+    assert_eq!(accounting::total(), b.capacity());
+    assert_eq!(accounting::buffers().len(), 1);
+
+    // files are not created, if they exceed the budget
+    #[cfg(target_os = "linux")]
+    {
+        let path =
+            std::env::temp_dir().join(format!("vmcircbuffer-accounting-{}", std::process::id()));
+        assert!(matches!(
+            DoubleMappedBuffer::<u8>::open_file(&path, 2 * pagesize(), Options::new()),
+            Err(DoubleMappedBufferError::Budget)
+        ));
+        assert!(!path.exists());
+        assert_eq!(accounting::total(), b.capacity());
+
+        accounting::set_budget(None);
+        drop(DoubleMappedBuffer::<u8>::open_file(&path, 2 * pagesize(), Options::new()).unwrap());
+        accounting::set_budget(Some(b.capacity() + pagesize()));
+
+        // existing files are checked before they are mapped and kept
+        assert!(matches!(
+            DoubleMappedBuffer::<u8>::open_existing_file(&path, Options::new()),
+            Err(DoubleMappedBufferError::Budget)
+        ));
+        assert!(path.exists());
+        assert_eq!(accounting::total(), b.capacity());
+        std::fs::remove_file(&path).unwrap();
+
+        let f = DoubleMappedBuffer::<u8>::open_file(&path, pagesize(), Options::new()).unwrap();
+        assert_eq!(accounting::total(), b.capacity() + pagesize());
+        drop(f);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    accounting::set_budget(None);
+    drop(b);
+    assert_eq!(accounting::total(), 0);
+}
@@ -90,3 +90,37 @@ fn fuzz_async() {
         }
     });
 }
+
+#[test]
+fn multi_wait() {
+    smol::block_on(async {
+        let w = asynchronous::Circular::new::<u32>().unwrap().into_multi();
+        let mut r = w.add_reader();
+        let capacity = w.capacity();
+
+        let full = w.reserve(capacity).await;
+        let tasks: Vec<_> = (0..2)
+            .map(|i| {
+                let w = w.clone();
+                smol::spawn(async move {
+                    let mut s = w.reserve(capacity / 2).await;
+                    s.fill(i);
+                    s.commit();
+                })
+            })
+            .collect();
+        full.commit();
+        drop(w);
+
+        let mut n = 0;
+        while let Some(s) = r.slice().await {
+            let l = s.len();
+            r.consume(l);
+            n += l;
+        }
+        for t in tasks {
+            t.await;
+        }
+        assert_eq!(n, 2 * capacity);
+    });
+}
@@ -0,0 +1,319 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vmcircbuffer::double_mapped_buffer::Advice;
+use vmcircbuffer::double_mapped_buffer::Options;
+use vmcircbuffer::generic::Circular;
+use vmcircbuffer::generic::CircularError;
+use vmcircbuffer::generic::NoMetadata;
+use vmcircbuffer::generic::Notifier;
+use vmcircbuffer::generic::Writer;
+
+struct MyNotifier;
+
+impl Notifier for MyNotifier {
+    fn arm(&mut self) {}
+    fn notify(&mut self) {}
+}
+
+fn writer() -> Writer<u32, MyNotifier, NoMetadata> {
+    Circular::with_capacity::<u32, MyNotifier, NoMetadata>(1).unwrap()
+}
+
+/// Wait until the background thread reclaimed the buffer of `w`, i.e., until
+/// the item that was produced last is no longer mirrored at the end of the
+/// output buffer. Gives up after some seconds.
+fn reclaimed(w: &mut Writer<u32, MyNotifier, NoMetadata>) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let s = w.slice(false);
+        let last = s[s.len() - 1];
+        // release the slice, without producing
+        w.produce(0, Vec::new());
+        if last == 0 {
+            return true;
+        }
+        if Instant::now() > deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Buffer that is reclaimed after `idle`, to find out that the background
+/// thread checked all buffers that were idle for less time.
+fn control(idle: Duration) -> Writer<u32, MyNotifier, NoMetadata> {
+    let mut w = writer();
+    w.reclaim_after(Some(idle));
+    w.slice(false)[0] = 42;
+    w.produce(1, Vec::new());
+    w
+}
+
+#[test]
+fn advise() {
+    let mut w = writer();
+    let mut r = w.add_reader(MyNotifier, MyNotifier);
+
+    w.slice(false)[0] = 42;
+    w.produce(1, Vec::new());
+    assert!(matches!(
+        w.advise(Advice::DontNeed),
+        Err(CircularError::NotEmpty)
+    ));
+
+    let (s, _) = r.slice(false).unwrap();
+    assert_eq!(s, &[42]);
+    r.consume(1);
+
+    for advice in [Advice::DontNeed, Advice::DontDump] {
+        match w.advise(advice) {
+            Ok(()) | Err(CircularError::Advise) => {}
+            Err(e) => panic!("unexpected error {e:?}"),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn reclaim_idle() {
+    let mut w = writer();
+    let mut r = w.add_reader(MyNotifier, MyNotifier);
+    w.reclaim_after(Some(Duration::from_millis(50)));
+
+    w.slice(false)[0] = 42;
+    w.produce(1, Vec::new());
+    assert!(reclaimed(&mut control(Duration::from_millis(100))));
+
+    // not reclaimed, since the reader did not read the item
+    assert_eq!(r.slice(false).unwrap().0, &[42]);
+    r.consume(1);
+    assert!(reclaimed(&mut w));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn reclaim_locked() {
+    let options = Options::new().lock(true);
+    // locking might exceed the limit of the process
+    let Ok(mut w) = Circular::with_options::<u32, MyNotifier, NoMetadata>(1, options) else {
+        return;
+    };
+    assert!(matches!(
+        w.advise(Advice::DontNeed),
+        Err(CircularError::Advise)
+    ));
+
+    w.reclaim_after(Some(Duration::from_millis(50)));
+    w.slice(false)[0] = 42;
+    w.produce(1, Vec::new());
+    assert!(reclaimed(&mut control(Duration::from_millis(100))));
+
+    let s = w.slice(false);
+    assert_eq!(s[s.len() - 1], 42);
+}
+
+#[test]
+fn reclaim_disabled() {
+    let mut w = writer();
+    w.reclaim_after(Some(Duration::from_millis(10)));
+    w.reclaim_after(None);
+
+    w.slice(false)[0] = 42;
+    w.produce(1, Vec::new());
+    // the control buffer is only reclaimed on Linux
+    reclaimed(&mut control(Duration::from_millis(100)));
+
+    let s = w.slice(false);
+    assert_eq!(s[s.len() - 1], 42);
+}
+
+struct Counted(Arc<AtomicUsize>);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn owned() {
+    let w = Circular::with_capacity::<String, MyNotifier, NoMetadata>(1).unwrap();
+    let (mut w, mut r) = w.into_owned(MyNotifier, MyNotifier).unwrap();
+    let capacity = w.slice(false).len();
+
+    for i in 0..capacity {
+        w.push(i.to_string(), false).unwrap();
+    }
+    assert_eq!(w.push("full".to_string(), false), Err("full".to_string()));
+
+    let mut items = r.take(false).unwrap();
+    assert_eq!(items.len(), capacity);
+    assert_eq!(items.next().unwrap(), "0");
+    assert_eq!(items.next().unwrap(), "1");
+
+    w.push("next".to_string(), false).unwrap();
+    assert_eq!(r.slice(false).unwrap()[0], "2");
+    let items: Vec<String> = r.take(false).unwrap().collect();
+    assert_eq!(items.len(), capacity - 1);
+    assert_eq!(items.last().unwrap(), "next");
+
+    drop(w);
+    assert!(r.take(false).is_none());
+}
+
+#[test]
+fn owned_drop() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let w = Circular::with_capacity::<Counted, MyNotifier, NoMetadata>(1).unwrap();
+    let (mut w, mut r) = w.into_owned(MyNotifier, MyNotifier).unwrap();
+
+    for _ in 0..10 {
+        assert!(w.push(Counted(drops.clone()), false).is_ok());
+    }
+    let space = w.slice(false).len();
+    w.resize(2 * space + 20).unwrap();
+
+    let mut items = r.take(false).unwrap();
+    drop(items.next());
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+    drop(r);
+    assert_eq!(drops.load(Ordering::SeqCst), 10);
+
+    assert!(w.push(Counted(drops.clone()), false).is_ok());
+    assert_eq!(drops.load(Ordering::SeqCst), 11);
+    drop(w);
+    assert_eq!(drops.load(Ordering::SeqCst), 11);
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let w = Circular::with_capacity::<Counted, MyNotifier, NoMetadata>(1).unwrap();
+    let (mut w, mut r) = w.into_owned(MyNotifier, MyNotifier).unwrap();
+    for _ in 0..5 {
+        assert!(w.push(Counted(drops.clone()), false).is_ok());
+    }
+    drop(w);
+    drop(r.take(false).unwrap().next());
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+    drop(r);
+    assert_eq!(drops.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn owned_forget_drain() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let w = Circular::with_capacity::<Counted, MyNotifier, NoMetadata>(1).unwrap();
+    let (mut w, mut r) = w.into_owned(MyNotifier, MyNotifier).unwrap();
+    for _ in 0..3 {
+        assert!(w.push(Counted(drops.clone()), false).is_ok());
+    }
+
+    let mut items = r.take(false).unwrap();
+    drop(items.next());
+    // must stay sound, if the iterator ever gets a destructor
+    #[allow(clippy::forget_non_drop)]
+    std::mem::forget(items);
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+    // the taken item is not handed out again
+    assert_eq!(r.take(false).unwrap().count(), 2);
+    assert_eq!(drops.load(Ordering::SeqCst), 3);
+    drop(w);
+    drop(r);
+    assert_eq!(drops.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn owned_resize_drain() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let w = Circular::with_capacity::<(Counted, usize), MyNotifier, NoMetadata>(1).unwrap();
+    let (mut w, mut r) = w.into_owned(MyNotifier, MyNotifier).unwrap();
+    for i in 0..4 {
+        assert!(w.push((Counted(drops.clone()), i), false).is_ok());
+    }
+    let space = w.slice(false).len();
+
+    let mut items = r.take(false).unwrap();
+    assert_eq!(items.next().unwrap().1, 0);
+    w.resize(2 * (space + 4)).unwrap();
+    assert_eq!(items.next().unwrap().1, 1);
+    w.resize(4 * (space + 4)).unwrap();
+    assert_eq!(items.next().unwrap().1, 2);
+    assert_eq!(drops.load(Ordering::SeqCst), 3);
+
+    let items: Vec<_> = r.take(false).unwrap().map(|(_, i)| i).collect();
+    assert_eq!(items, [3]);
+    drop(w);
+    drop(r);
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn owned_readers() {
+    let w = writer();
+    let _r = w.add_reader(MyNotifier, MyNotifier);
+    assert!(matches!(
+        w.into_owned(MyNotifier, MyNotifier),
+        Err(CircularError::Readers)
+    ));
+}
+
+#[test]
+fn batch() {
+    let mut w = writer();
+    let mut r = w.add_reader(MyNotifier, MyNotifier);
+
+    let batch = w.batch(false);
+    let len = batch.len();
+    let mut a = batch.split(10);
+    let mut b = batch.split(20);
+    let mut c = batch.split(30);
+    assert_eq!(batch.remaining(), len - 60);
+
+    c.fill(3);
+    c.commit(Vec::new());
+    b.fill(2);
+    b.commit(Vec::new());
+    assert_eq!(batch.produced(), 0);
+    a.fill(1);
+    a.commit(Vec::new());
+    assert_eq!(batch.produced(), 60);
+    // items after an uncommitted chunk are not produced
+    let _ = batch.split(5);
+    batch.split(1).commit(Vec::new());
+    assert_eq!(batch.produced(), 60);
+    drop(batch);
+
+    let (s, _) = r.slice(false).unwrap();
+    assert_eq!(s.len(), 60);
+    assert!(s[0..10].iter().all(|&v| v == 1));
+    assert!(s[10..30].iter().all(|&v| v == 2));
+    assert!(s[30..60].iter().all(|&v| v == 3));
+}
+
+#[test]
+fn batch_threads() {
+    let mut w = writer();
+    let mut r = w.add_reader(MyNotifier, MyNotifier);
+
+    let batch = w.batch(false);
+    let len = batch.len();
+    std::thread::scope(|s| {
+        for mut chunk in batch.chunks(100).into_iter().rev() {
+            s.spawn(move || {
+                let start = chunk.as_ptr() as usize;
+                for (i, v) in chunk.iter_mut().enumerate() {
+                    *v = (start / 4 + i) as u32;
+                }
+                chunk.commit(Vec::new());
+            });
+        }
+    });
+    assert_eq!(batch.produced(), len);
+    drop(batch);
+
+    let (s, _) = r.slice(false).unwrap();
+    assert_eq!(s.len(), len);
+    assert!(s.windows(2).all(|v| v[1] == v[0] + 1));
+}
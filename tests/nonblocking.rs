@@ -147,3 +147,33 @@ fn minimal() {
         assert_eq!(*v, 123);
     }
 }
+
+#[test]
+fn multi_order() {
+    let w = Circular::new::<u32>().unwrap().into_multi();
+    let mut r = w.add_reader();
+
+    let mut a = w.try_reserve(2).unwrap();
+    let mut b = w.clone().try_reserve(1).unwrap();
+    let mut c = w.try_reserve(1).unwrap();
+    a.fill(1);
+    b.fill(2);
+    c.fill(3);
+
+    c.commit();
+    assert_eq!(r.try_slice().unwrap(), &[]);
+    a.commit();
+    assert_eq!(r.try_slice().unwrap(), &[1, 1]);
+    // dropped reservations that are followed by others are committed zeroed
+    drop(b);
+    assert_eq!(r.try_slice().unwrap(), &[1, 1, 0, 3]);
+    r.consume(4);
+
+    // the last reservation is released, when it is dropped
+    let capacity = w.capacity();
+    let d = w.try_reserve(capacity).unwrap();
+    assert!(w.try_reserve(1).is_none());
+    drop(d);
+    assert_eq!(r.try_slice().unwrap(), &[]);
+    assert!(w.try_reserve(capacity).is_some());
+}
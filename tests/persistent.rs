@@ -0,0 +1,156 @@
+#![cfg(target_os = "linux")]
+
+use std::path::PathBuf;
+
+use vmcircbuffer::double_mapped_buffer::DoubleMappedBufferError;
+use vmcircbuffer::persistent::PersistentError;
+use vmcircbuffer::persistent::Reader;
+use vmcircbuffer::persistent::Writer;
+use vmcircbuffer::persistent::MAX_READERS;
+
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn new(test: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "vmcircbuffer-persistent-{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        TempPath(path)
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn write(w: &mut Writer<u32>, items: std::ops::Range<u32>) {
+    let s = w.try_slice();
+    let n = items.len();
+    for (v, i) in s.iter_mut().zip(items) {
+        *v = i;
+    }
+    w.produce(n);
+}
+
+#[test]
+fn resume() {
+    let path = TempPath::new("resume");
+    let mut w = Writer::<u32>::open(&path.0, 1234).unwrap();
+    let mut r = Reader::<u32>::open(&path.0, 3).unwrap();
+    assert_eq!(w.readers(), 1);
+
+    write(&mut w, 0..10);
+    let s = r.try_slice().unwrap();
+    assert_eq!(s, (0..10).collect::<Vec<_>>());
+    r.consume(4);
+    w.flush().unwrap();
+    r.flush().unwrap();
+    drop(r);
+    drop(w);
+
+    let mut w = Writer::<u32>::open(&path.0, 1).unwrap();
+    assert_eq!(w.offset(), 10);
+    write(&mut w, 10..20);
+    drop(w);
+
+    let mut r = Reader::<u32>::open(&path.0, 3).unwrap();
+    assert_eq!(r.offset(), 4);
+    let s = r.try_slice().unwrap();
+    assert_eq!(s, (4..20).collect::<Vec<_>>());
+    r.consume(16);
+    assert!(r.try_slice().is_none());
+}
+
+#[test]
+fn flight_recorder() {
+    let path = TempPath::new("flight_recorder");
+    let mut w = Writer::<u32>::open(&path.0, 1).unwrap();
+    let capacity = w.try_slice().len() as u32;
+
+    write(&mut w, 0..capacity);
+    write(&mut w, capacity..capacity + 10);
+    assert_eq!(w.try_slice().len(), capacity as usize);
+
+    let mut r = Reader::<u32>::open(&path.0, 0).unwrap();
+    assert_eq!(r.offset(), 10);
+    let s = r.try_slice().unwrap();
+    assert_eq!(s, (10..capacity + 10).collect::<Vec<_>>());
+    assert!(w.try_slice().is_empty());
+
+    r.consume(5);
+    assert_eq!(w.try_slice().len(), 5);
+}
+
+#[test]
+fn attached_twice() {
+    let path = TempPath::new("attached_twice");
+    let _w = Writer::<u32>::open(&path.0, 1).unwrap();
+    assert!(matches!(
+        Writer::<u32>::open(&path.0, 1),
+        Err(PersistentError::WriterActive)
+    ));
+
+    let _r = Reader::<u32>::open(&path.0, 1).unwrap();
+    assert!(matches!(
+        Reader::<u32>::open(&path.0, 1),
+        Err(PersistentError::ReaderActive)
+    ));
+    assert!(matches!(
+        Reader::<u32>::open(&path.0, MAX_READERS),
+        Err(PersistentError::InvalidReader)
+    ));
+}
+
+#[test]
+fn wrong_type() {
+    let path = TempPath::new("wrong_type");
+    drop(Writer::<u32>::open(&path.0, 1).unwrap());
+    assert!(matches!(
+        Writer::<u64>::open(&path.0, 1),
+        Err(PersistentError::Buffer(DoubleMappedBufferError::Layout))
+    ));
+}
+
+#[test]
+fn missing_file() {
+    let path = TempPath::new("missing_file");
+    match Reader::<u32>::open(&path.0, 0) {
+        Err(PersistentError::Buffer(DoubleMappedBufferError::Open(e))) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::NotFound)
+        }
+        _ => panic!("opened missing file"),
+    }
+    assert!(!path.0.exists());
+}
+
+#[test]
+fn stale_attachments() {
+    let path = TempPath::new("stale_attachments");
+    let copy = TempPath::new("stale_attachments_copy");
+    let mut w = Writer::<u32>::open(&path.0, 1).unwrap();
+    let mut r = Reader::<u32>::open(&path.0, 0).unwrap();
+    let _blocking = Reader::<u32>::open(&path.0, 1).unwrap();
+    let all = w.try_slice().len();
+    w.produce(all);
+    let n = r.try_slice().unwrap().len();
+    r.consume(n);
+    assert!(w.try_slice().is_empty());
+
+    // the copy looks like the file after a reboot: attached, but not locked
+    w.flush().unwrap();
+    std::fs::copy(&path.0, &copy.0).unwrap();
+
+    let mut r = Reader::<u32>::open(&copy.0, 0).unwrap();
+    assert!(r.try_slice().is_none());
+    drop(r);
+
+    let mut w = Writer::<u32>::open(&copy.0, 1).unwrap();
+    assert_eq!(w.readers(), 1);
+    assert_eq!(w.try_slice().len(), all);
+    assert_eq!(w.readers(), 0);
+}
@@ -0,0 +1,162 @@
+#![cfg(target_os = "linux")]
+
+use std::process::Command;
+
+use vmcircbuffer::double_mapped_buffer::DoubleMappedBuffer;
+use vmcircbuffer::shared::Reader;
+use vmcircbuffer::shared::SharedError;
+use vmcircbuffer::shared::Writer;
+use vmcircbuffer::shared::MAX_READERS;
+
+const CHILD_ENV: &str = "VMCIRCBUFFER_SHARED_CHILD";
+
+fn name(test: &str) -> String {
+    format!("vmcircbuffer-shared-{}-{}", test, std::process::id())
+}
+
+fn run_child(test: &str, name: &str) {
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", test, "--ignored", "--nocapture"])
+        .env(CHILD_ENV, name)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn produce_consume() {
+    let name = name("produce_consume");
+    let mut w = Writer::<u32>::create(&name, 1234).unwrap();
+    let mut r = Reader::<u32>::open(&name).unwrap();
+    assert_eq!(w.readers(), 1);
+
+    let s = w.try_slice();
+    let all = s.len();
+    for (i, v) in s.iter_mut().enumerate() {
+        *v = i as u32;
+    }
+    w.produce(100);
+    assert_eq!(w.try_slice().len(), all - 100);
+
+    let s = r.try_slice().unwrap();
+    assert_eq!(s.len(), 100);
+    for (i, v) in s.iter().enumerate() {
+        assert_eq!(*v, i as u32);
+    }
+    r.consume(100);
+    assert_eq!(w.try_slice().len(), all);
+
+    drop(r);
+    assert_eq!(w.readers(), 0);
+}
+
+#[test]
+fn writer_dropped() {
+    let name = name("writer_dropped");
+    let mut w = Writer::<u8>::create(&name, 1).unwrap();
+    let mut r = Reader::<u8>::open(&name).unwrap();
+
+    w.slice()[0] = 23;
+    w.produce(1);
+    drop(w);
+
+    assert!(Reader::<u8>::open(&name).is_err());
+    assert_eq!(r.slice().unwrap(), &[23]);
+    r.consume(1);
+    assert!(r.slice().is_none());
+}
+
+#[test]
+fn too_many_readers() {
+    let name = name("too_many_readers");
+    let _w = Writer::<u8>::create(&name, 1).unwrap();
+    let mut readers = Vec::new();
+    for _ in 0..MAX_READERS {
+        readers.push(Reader::<u8>::open(&name).unwrap());
+    }
+    assert!(matches!(
+        Reader::<u8>::open(&name),
+        Err(SharedError::NoReaderSlot)
+    ));
+    readers.pop();
+    assert!(Reader::<u8>::open(&name).is_ok());
+}
+
+#[test]
+fn threads() {
+    let name = name("threads");
+    let mut w = Writer::<u32>::create(&name, 1).unwrap();
+    let mut r = Reader::<u32>::open(&name).unwrap();
+    let n_items = 1_000_000;
+
+    let writer = std::thread::spawn(move || {
+        let mut i = 0;
+        while i < n_items {
+            let s = w.slice();
+            let n = std::cmp::min(s.len(), (n_items - i) as usize);
+            for (k, v) in s[0..n].iter_mut().enumerate() {
+                *v = i + k as u32;
+            }
+            w.produce(n);
+            i += n as u32;
+        }
+    });
+
+    let mut i = 0;
+    while let Some(s) = r.slice() {
+        for v in s {
+            assert_eq!(*v, i);
+            i += 1;
+        }
+        let n = s.len();
+        r.consume(n);
+    }
+    assert_eq!(i, n_items);
+    writer.join().unwrap();
+}
+
+#[test]
+#[ignore]
+fn crashed_reader_child() {
+    if let Ok(name) = std::env::var(CHILD_ENV) {
+        let r = Reader::<u32>::open(&name).unwrap();
+        std::mem::forget(r);
+        std::process::exit(0);
+    }
+}
+
+#[test]
+fn crashed_reader() {
+    let name = name("crashed_reader");
+    let mut w = Writer::<u32>::create(&name, 1).unwrap();
+    run_child("crashed_reader_child", &name);
+
+    assert_eq!(w.readers(), 1);
+    let all = w.try_slice().len();
+    w.produce(all);
+    assert!(w.try_slice().is_empty());
+
+    // blocks until the crashed reader is detected
+    assert_eq!(w.slice().len(), all);
+    assert_eq!(w.readers(), 0);
+}
+
+#[test]
+#[ignore]
+fn crashed_writer_child() {
+    if let Ok(name) = std::env::var(CHILD_ENV) {
+        let w = Writer::<u32>::create(&name, 1).unwrap();
+        std::mem::forget(w);
+        std::process::exit(0);
+    }
+}
+
+#[test]
+fn crashed_writer() {
+    let name = name("crashed_writer");
+    run_child("crashed_writer_child", &name);
+
+    let mut r = Reader::<u32>::open(&name).unwrap();
+    assert!(r.slice().is_none());
+    DoubleMappedBuffer::<u32>::unlink_named(&name).unwrap();
+}
@@ -0,0 +1,141 @@
+use rand::distributions::{Distribution, Uniform};
+use std::iter::repeat_with;
+use std::time::Duration;
+
+use vmcircbuffer::spsc::asynchronous;
+use vmcircbuffer::spsc::nonblocking;
+use vmcircbuffer::spsc::sync;
+
+#[test]
+fn threads() {
+    let (mut w, mut r) = sync::Circular::new::<u32>().unwrap();
+    let input: Vec<u32> = repeat_with(rand::random::<u32>).take(1231233).collect();
+
+    let expected = input.clone();
+    let writer = std::thread::spawn(move || {
+        let mut rng = rand::thread_rng();
+        let mut off = 0;
+        while off < input.len() {
+            let s = w.slice();
+            let n = std::cmp::min(s.len(), input.len() - off);
+            let n = std::cmp::min(n, Uniform::from(1..=s.len()).sample(&mut rng));
+            s[0..n].copy_from_slice(&input[off..off + n]);
+            w.produce(n);
+            off += n;
+        }
+    });
+
+    let mut output = Vec::new();
+    while let Some(s) = r.slice() {
+        output.extend_from_slice(s);
+        let l = s.len();
+        r.consume(l);
+    }
+    writer.join().unwrap();
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn wait_writer() {
+    let (mut w, mut r) = sync::Circular::new::<u8>().unwrap();
+    let l = w.slice().len();
+    w.produce(l);
+    assert!(w.try_slice().is_empty());
+
+    let reader = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        let l = r.slice().unwrap().len();
+        r.consume(l);
+        r
+    });
+    assert_eq!(w.slice().len(), l);
+    let _r = reader.join().unwrap();
+}
+
+#[test]
+fn dropped() {
+    let (mut w, mut r) = sync::Circular::new::<u8>().unwrap();
+    assert!(w.try_slice().len() >= 10);
+    w.produce(10);
+    drop(w);
+    assert_eq!(r.slice().unwrap().len(), 10);
+    r.consume(10);
+    assert!(r.slice().is_none());
+
+    let (mut w, r) = sync::Circular::new::<u8>().unwrap();
+    let l = w.slice().len();
+    w.produce(l);
+    drop(r);
+    assert_eq!(w.slice().len(), l);
+}
+
+#[test]
+fn uninit() {
+    let (mut w, mut r) = nonblocking::Circular::new::<bool>().unwrap();
+
+    let s = unsafe { w.try_slice_uninit() };
+    for v in s.iter_mut().take(3) {
+        v.write(true);
+    }
+    unsafe { w.assume_init(3) };
+
+    assert_eq!(r.try_slice().unwrap(), &[true; 3]);
+    r.consume(3);
+    assert_eq!(r.try_slice().unwrap().len(), 0);
+}
+
+#[test]
+fn fuzz_nonblocking() {
+    let (mut w, mut r) = nonblocking::Circular::new::<u32>().unwrap();
+    let size = w.try_slice().len();
+
+    let input: Vec<u32> = repeat_with(rand::random::<u32>).take(1231233).collect();
+
+    let mut rng = rand::thread_rng();
+    let n_writes_dist = Uniform::from(0..4);
+    let n_samples_dist = Uniform::from(0..size / 2);
+
+    let mut w_off = 0;
+    let mut r_off = 0;
+
+    while r_off < input.len() {
+        let n_writes = n_writes_dist.sample(&mut rng);
+        for _ in 0..n_writes {
+            let s = w.try_slice();
+            let n = std::cmp::min(s.len(), input.len() - w_off);
+            let n = std::cmp::min(n, n_samples_dist.sample(&mut rng));
+
+            s[0..n].copy_from_slice(&input[w_off..w_off + n]);
+            w.produce(n);
+            w_off += n;
+        }
+
+        let s = r.try_slice().unwrap();
+        assert_eq!(s.len(), w_off - r_off);
+        assert_eq!(s, &input[r_off..w_off]);
+        let l = s.len();
+        r.consume(l);
+        r_off += l;
+    }
+}
+
+#[test]
+fn wait_async() {
+    smol::block_on(async {
+        let (mut w, mut r) = asynchronous::Circular::new::<u32>().unwrap();
+        let delay = Duration::from_millis(100);
+
+        smol::spawn(async move {
+            smol::Timer::after(delay).await;
+            let s = w.slice().await;
+            s[0..10].fill(23);
+            w.produce(10);
+        })
+        .detach();
+
+        let s = r.slice().await.unwrap();
+        assert_eq!(s, &[23; 10]);
+        r.consume(10);
+        assert!(r.slice().await.is_none());
+    });
+}
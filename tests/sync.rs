@@ -186,3 +186,258 @@ fn fuzz_sync() {
         r_off += l;
     }
 }
+
+#[cfg(unix)]
+#[test]
+fn imported_buffer() {
+    use vmcircbuffer::double_mapped_buffer::{
+        pagesize, Access, DoubleMappedBuffer, Options, SystemBackend,
+    };
+    use vmcircbuffer::generic::CircularError;
+
+    let options = Options::new().keep_fd(true);
+    let ring = DoubleMappedBuffer::<u32>::new_in::<SystemBackend>(2 * pagesize(), options).unwrap();
+    let fd = || ring.fd().unwrap().try_clone_to_owned().unwrap();
+    let len = ring.capacity() * std::mem::size_of::<u32>();
+
+    let buffer = DoubleMappedBuffer::<u32>::from_fd_range(fd(), 0, len, Access::ReadWrite).unwrap();
+    let mut w = Circular::with_buffer(buffer).unwrap();
+    let mut r = w.add_reader();
+
+    let s = w.slice();
+    assert_eq!(s.len(), ring.capacity());
+    s[0] = 42;
+    w.produce(1);
+    assert_eq!(r.slice().unwrap(), &[42]);
+    assert_eq!(unsafe { ring.slice()[0] }, 42);
+
+    let buffer = DoubleMappedBuffer::<u32>::from_fd_range(fd(), 0, len, Access::ReadOnly).unwrap();
+    assert!(matches!(
+        Circular::with_buffer(buffer),
+        Err(CircularError::ReadOnly)
+    ));
+}
+
+#[test]
+fn custom_backend() {
+    use std::any::Any;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use vmcircbuffer::double_mapped_buffer::{
+        Backend, DoubleMappedBufferError, Options, SystemBackend,
+    };
+
+    static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Counting(SystemBackend);
+
+    unsafe impl Backend for Counting {
+        fn allocate(
+            min_items: usize,
+            item_size: usize,
+            alignment: usize,
+            options: &Options,
+        ) -> Result<Self, DoubleMappedBufferError> {
+            ALLOCATED.fetch_add(1, Ordering::SeqCst);
+            SystemBackend::allocate(min_items, item_size, alignment, options).map(Counting)
+        }
+        fn addr(&self) -> usize {
+            self.0.addr()
+        }
+        fn size_bytes(&self) -> usize {
+            self.0.size_bytes()
+        }
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    let mut w = Circular::with_backend::<u32, Counting>(123, Options::new()).unwrap();
+    let mut r = w.add_reader();
+    assert_eq!(ALLOCATED.load(Ordering::SeqCst), 1);
+
+    let s = w.slice();
+    s[0] = 7;
+    w.produce(1);
+    assert_eq!(r.slice().unwrap(), &[7]);
+}
+
+#[test]
+fn resize() {
+    let mut w = Circular::with_capacity::<u32>(1).unwrap();
+    let mut r1 = w.add_reader();
+    let mut r2 = w.add_reader();
+
+    let s = w.try_slice();
+    let capacity = s.len();
+    for (i, v) in s.iter_mut().enumerate() {
+        *v = i as u32;
+    }
+    w.produce(capacity);
+    assert_eq!(r1.slice().unwrap().len(), capacity);
+    r1.consume(10);
+
+    // the slice stays valid while the buffer is resized
+    let s = r1.slice().unwrap();
+    w.resize(4 * capacity).unwrap();
+    assert_eq!(s, (10..capacity as u32).collect::<Vec<_>>());
+    let n = s.len();
+    r1.consume(n);
+
+    let s = r2.slice().unwrap();
+    assert_eq!(s, (0..capacity as u32).collect::<Vec<_>>());
+    r2.consume(5);
+
+    let s = w.try_slice();
+    assert!(s.len() >= 3 * capacity);
+    for (i, v) in s.iter_mut().enumerate() {
+        *v = (capacity + i) as u32;
+    }
+    let n = s.len();
+    w.produce(n);
+
+    let s = r2.slice().unwrap();
+    assert_eq!(s, (5..(capacity + n) as u32).collect::<Vec<_>>());
+    let n2 = s.len();
+    r2.consume(n2);
+    let s = r1.slice().unwrap();
+    assert_eq!(s.len(), n);
+    r1.consume(n);
+
+    // shrink an empty buffer
+    w.resize(1).unwrap();
+    assert_eq!(w.try_slice().len(), capacity);
+}
+
+#[test]
+fn resize_imported() {
+    use vmcircbuffer::double_mapped_buffer::DoubleMappedBuffer;
+    use vmcircbuffer::generic::CircularError;
+
+    let buffer = DoubleMappedBuffer::<u32>::new(1).unwrap();
+    let mut w = Circular::with_buffer(buffer).unwrap();
+    assert!(matches!(w.resize(1234), Err(CircularError::Resize)));
+}
+
+#[test]
+fn grow_when_full() {
+    use vmcircbuffer::generic::GrowPolicy;
+
+    let mut w = Circular::with_capacity::<u32>(1).unwrap();
+    let mut r = w.add_reader();
+    let capacity = w.try_slice().len();
+    w.grow_when_full(Some(GrowPolicy::new(2 * capacity).blocked(2)));
+
+    w.produce(capacity);
+    assert!(w.try_slice().is_empty());
+    assert_eq!(w.try_slice().len(), capacity);
+    w.produce(capacity);
+    assert!(w.try_slice().is_empty());
+    assert!(w.try_slice().is_empty());
+
+    assert_eq!(r.slice().unwrap().len(), 2 * capacity);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn allocation_error() {
+    use vmcircbuffer::double_mapped_buffer::{
+        pagesize, DoubleMappedBufferError, NumaPolicy, Options,
+    };
+    use vmcircbuffer::generic::CircularError;
+
+    let options = Options::new().numa(NumaPolicy::Bind(1000));
+    match Circular::with_options::<u32>(1000, options) {
+        Err(CircularError::Allocation {
+            error,
+            requested,
+            rounded,
+        }) => {
+            assert!(matches!(error, DoubleMappedBufferError::Numa(_)));
+            assert!(error.io_error().unwrap().raw_os_error().is_some());
+            assert_eq!(requested, 4000);
+            assert_eq!(rounded, pagesize());
+        }
+        _ => panic!("expected allocation error"),
+    }
+}
+
+vmcircbuffer::pod! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(C)]
+    struct Complex {
+        re: f32,
+        im: f32,
+    }
+}
+
+#[test]
+fn pod_struct() {
+    let mut w = Circular::new::<Complex>().unwrap();
+    let mut r = w.add_reader();
+
+    let s = w.slice();
+    s[0] = Complex { re: 1.0, im: -1.0 };
+    w.produce(1);
+
+    assert_eq!(r.slice().unwrap(), &[Complex { re: 1.0, im: -1.0 }]);
+}
+
+#[test]
+fn uninit() {
+    let mut w = Circular::new::<bool>().unwrap();
+    let mut r = w.add_reader();
+
+    let s = unsafe { w.slice_uninit() };
+    for v in s.iter_mut().take(3) {
+        v.write(true);
+    }
+    unsafe { w.assume_init(3) };
+
+    assert_eq!(r.slice().unwrap(), &[true; 3]);
+}
+
+#[test]
+fn multi_producer() {
+    let w = Circular::new::<u64>().unwrap().into_multi();
+    let mut r = w.add_reader();
+    let n_threads = 4;
+    let n_items = 100_000;
+
+    let producers: Vec<_> = (0..n_threads)
+        .map(|t| {
+            let w = w.clone();
+            std::thread::spawn(move || {
+                let mut rng = rand::thread_rng();
+                let mut i = 0;
+                while i < n_items {
+                    let n = std::cmp::min(Uniform::from(1..64).sample(&mut rng), n_items - i);
+                    let mut s = w.reserve(n as usize);
+                    for v in s.iter_mut() {
+                        *v = (t << 32) | i;
+                        i += 1;
+                    }
+                    if rand::random::<bool>() {
+                        std::thread::yield_now();
+                    }
+                    s.commit();
+                }
+            })
+        })
+        .collect();
+    drop(w);
+
+    let mut next = vec![0; n_threads as usize];
+    while let Some(s) = r.slice() {
+        for v in s {
+            let t = (v >> 32) as usize;
+            assert_eq!(v & 0xffff_ffff, next[t]);
+            next[t] += 1;
+        }
+        let l = s.len();
+        r.consume(l);
+    }
+    for p in producers {
+        p.join().unwrap();
+    }
+    assert_eq!(next, vec![n_items; n_threads as usize]);
+}